@@ -1,11 +1,12 @@
 use cosmwasm_schema::write_api;
 
-use otc_wasm::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use otc_wasm::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 fn main() {
     write_api! {
         instantiate: InstantiateMsg,
         query: QueryMsg,
         execute: ExecuteMsg,
+        migrate: MigrateMsg,
     }
 }
@@ -13,12 +13,58 @@ pub enum ContractError {
     Denom(#[from] DenomError),
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Contract is paused")]
+    Paused,
     #[error("Deal not found")]
     DealNotFound,
+    #[error("Fee can't exceed {0} basis points")]
+    InvalidFee(u16),
     #[error("Duration must be between {0} and {1} seconds")]
     InvalidDuration(u64, u64),
+    #[error("Duration must be one of {0:?} seconds")]
+    DurationNotAllowed(Vec<u64>),
+    #[error("Invalid duration policy: {0}")]
+    InvalidDurationPolicy(String),
     #[error("Deal is expired")]
     DealExpired,
+    #[error("Deal is restricted to designated buyers")]
+    BuyerNotAllowed,
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner,
+    #[error("Ownership transfer is expired")]
+    OwnershipTransferExpired,
+    #[error("Expiration is already reached")]
+    InvalidExpiration,
+    #[error("Bid not found")]
+    BidNotFound,
+    #[error("Bid is expired")]
+    BidExpired,
+    #[error("Bid is restricted to a designated seller")]
+    SellerNotAllowed,
     #[error("Insufficient Amount expected {0}")]
     InsufficientAmount(String),
+    #[error("Offer must match the funds sent")]
+    InvalidOffer,
+    #[error("Denom {0} can't be traded")]
+    DenomNotAllowed(String),
+    #[error("{0} is below the minimum of {1}")]
+    AmountBelowMinimum(String, String),
+    #[error("{0} is above the maximum of {1}")]
+    AmountAboveMaximum(String, String),
+    #[error("Minimum amount can't exceed the maximum amount")]
+    InvalidTradeLimit,
+    #[error("Invalid ask: {0}")]
+    InvalidAsk(String),
+    #[error("Partial fills require a single fungible asset on each side")]
+    PartialFillNotSupported,
+    #[error("Payment exceeds the remaining ask {0}")]
+    ExceedsRemaining(String),
+    #[error("Payment is too small to receive any of the offer")]
+    FillTooSmall,
+    #[error("Invalid contract version {0}")]
+    InvalidVersion(String),
+    #[error("Can't migrate from contract {0}")]
+    InvalidMigration(String),
+    #[error("Can't downgrade from version {0} to {1}")]
+    CannotDowngrade(String, String),
 }
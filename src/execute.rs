@@ -2,14 +2,33 @@
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
-    ensure, ensure_eq, BankMsg, CosmosMsg, DepsMut, Env, Event, MessageInfo, Response,
+    attr, ensure, ensure_eq, from_json, Addr, Attribute, CosmosMsg, Deps, DepsMut, Empty, Env,
+    Event, MessageInfo, Order, Response, StdResult, Storage, Uint128,
 };
-use cw_denom::validate_native_denom;
-use cw_utils::{must_pay, one_coin, Expiration};
+use cw20::Cw20ReceiveMsg;
+use cw_denom::{validate_native_denom, CheckedDenom, UncheckedDenom};
+use cw_storage_plus::{Bound, PrimaryKey};
+use cw_utils::{nonpayable, one_coin, Expiration, PaymentError};
+use std::collections::BTreeMap;
+use std::slice;
 
 use crate::error::ContractError;
-use crate::msg::{CreateDealMsg, ExecuteMsg};
-use crate::state::{deals, next_id, Deal, DealStatus, Id, CONFIG};
+use crate::migrate::migrate_deals;
+use crate::msg::{
+    CreateBidMsg, CreateDealMsg, Cw721ReceiveMsg, ExecuteMsg, OwnershipAction, ReceiveMsg,
+    ReceiveNftMsg, UpdateConfigMsg,
+};
+use crate::state::{
+    add_volume, bids, count_deal_status, deals, display_assets, native_assets, next_bid_id,
+    next_id, next_trade_id, trades, transfer_msgs, update_balances, Asset, Bid, Config, Deal,
+    DealStatus, DenomList, Id, Nft, Offer, Ownership, Trade, TradeLimit, UncheckedAsset,
+    ALLOWED_DENOMS, ASK_VOLUME, BUYER_DEALS, CONFIG, COUNTERPARTY_DEALS, DENIED_DENOMS,
+    OFFER_VOLUME, OWNERSHIP, PAUSED, TRADE_LIMITS,
+};
+
+const BPS_DENOMINATOR: u128 = 10_000;
+const DEFAULT_PROCESS_LIMIT: u32 = 10;
+const MAX_PROCESS_LIMIT: u32 = 30;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
@@ -24,10 +43,89 @@ pub fn execute(
         ExecuteMsg::CreateDeal(created_deal_msg) => create_deal(deps, env, info, created_deal_msg),
         ExecuteMsg::ExecuteDeal(id) => execute_deal(deps, env, info, id),
         ExecuteMsg::CancelDeal(id) => cancel_deal(deps, env, info, id),
-        ExecuteMsg::UpdateConfig {
-            owner,
-            duration_range,
-        } => update_config(deps, info, owner, duration_range),
+        ExecuteMsg::UpdateConfig(update_config_msg) => update_config(deps, info, update_config_msg),
+        ExecuteMsg::UpdateOwnership(action) => update_ownership(deps, env, info, action),
+        ExecuteMsg::Pause => pause(deps, info),
+        ExecuteMsg::Unpause => unpause(deps, info),
+        ExecuteMsg::RemovePauser => remove_pauser(deps, info),
+        ExecuteMsg::SetTradeLimit { denom, limit } => set_trade_limit(deps, info, denom, limit),
+        ExecuteMsg::UpdateDenoms { list, add, remove } => {
+            update_denoms(deps, info, list, add, remove)
+        }
+        ExecuteMsg::Receive(cw20_msg) => receive(deps, env, info, cw20_msg),
+        ExecuteMsg::ReceiveNft(cw721_msg) => receive_nft(deps, env, info, cw721_msg),
+        ExecuteMsg::CreateBid(create_bid_msg) => create_bid(deps, env, info, create_bid_msg),
+        ExecuteMsg::ExecuteBid(id) => execute_bid(deps, env, info, id),
+        ExecuteMsg::CancelBid(id) => cancel_bid(deps, env, info, id),
+        ExecuteMsg::WithdrawBid(id) => withdraw_bid(deps, env, info, id),
+        ExecuteMsg::ClaimBid(id) => claim_bid(deps, info, id),
+        ExecuteMsg::ProcessExpired { limit } => process_expired(deps, env, info, limit),
+        ExecuteMsg::MigrateDeals { limit } => migrate_deals(deps, info, limit),
+    }
+}
+
+pub fn receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+
+    let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+
+    match from_json(&cw20_msg.msg)? {
+        ReceiveMsg::CreateDeal(msg) => {
+            ensure!(msg.offer.is_empty(), ContractError::InvalidOffer);
+            let offer = Asset {
+                denom: CheckedDenom::Cw20(info.sender),
+                amount: cw20_msg.amount,
+            };
+            save_deal(deps, env, sender, Offer::Fungible(vec![offer]), msg)
+        }
+        ReceiveMsg::ExecuteDeal(id) => {
+            let payment = Asset {
+                denom: CheckedDenom::Cw20(info.sender),
+                amount: cw20_msg.amount,
+            };
+            fill_deal(deps, env, sender, id, vec![payment])
+        }
+        ReceiveMsg::CreateBid(msg) => {
+            let offer = Asset {
+                denom: CheckedDenom::Cw20(info.sender),
+                amount: cw20_msg.amount,
+            };
+            save_bid(deps, env, sender, offer, msg)
+        }
+        ReceiveMsg::ExecuteBid(id) => {
+            let payment = Asset {
+                denom: CheckedDenom::Cw20(info.sender),
+                amount: cw20_msg.amount,
+            };
+            fill_bid(deps, env, sender, id, payment)
+        }
+    }
+}
+
+pub fn receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw721_msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+
+    let sender = deps.api.addr_validate(&cw721_msg.sender)?;
+
+    match from_json(&cw721_msg.msg)? {
+        ReceiveNftMsg::CreateDeal(msg) => {
+            ensure!(msg.offer.is_empty(), ContractError::InvalidOffer);
+            let offer = Nft {
+                collection: info.sender,
+                token_id: cw721_msg.token_id,
+            };
+            save_deal(deps, env, sender, Offer::Nft(offer), msg)
+        }
     }
 }
 
@@ -37,7 +135,7 @@ pub fn withdraw(
     info: MessageInfo,
     id: Id,
 ) -> Result<Response, ContractError> {
-    let deal = deals().load(deps.storage, id)?;
+    let mut deal = deals().load(deps.storage, id)?;
 
     ensure_eq!(info.sender, deal.seller, ContractError::Unauthorized);
 
@@ -48,22 +146,25 @@ pub fn withdraw(
         ContractError::Unauthorized
     );
 
-    deals().update(deps.storage, id, |d| -> Result<Deal, ContractError> {
-        let mut deal = d.unwrap();
-        deal.status = DealStatus::Expired;
-        Ok(deal)
-    })?;
-
-    let msg = BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: vec![deal.offer],
-    };
+    let config = CONFIG.load(deps.storage)?;
+    let previous = deal.clone();
+    let (msgs, attributes) = refund_msgs(&config, &mut deal)?;
+    deal.status = DealStatus::Expired;
+    deals().save(deps.storage, id, &deal)?;
+    count_deal_status(
+        deps.storage,
+        &deal.seller,
+        Some(&DealStatus::Open),
+        &deal.status,
+    )?;
+    update_balances(deps.storage, Some(&previous), &deal)?;
 
     let event = Event::new("OtcWasm.v1.MsgWithdraw")
         .add_attribute("seller", info.sender)
-        .add_attribute("id", id.to_string());
+        .add_attribute("id", id.to_string())
+        .add_attributes(attributes);
 
-    Ok(Response::new().add_event(event).add_message(msg))
+    Ok(Response::new().add_event(event).add_messages(msgs))
 }
 
 pub fn execute_deal(
@@ -72,7 +173,21 @@ pub fn execute_deal(
     info: MessageInfo,
     id: Id,
 ) -> Result<Response, ContractError> {
-    let deal = deals().load(deps.storage, id)?;
+    ensure!(!info.funds.is_empty(), PaymentError::NoFunds {});
+
+    fill_deal(deps, env, info.sender, id, native_assets(&info.funds))
+}
+
+fn fill_deal(
+    deps: DepsMut,
+    env: Env,
+    buyer: Addr,
+    id: Id,
+    payment: Vec<Asset>,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref())?;
+
+    let mut deal = deals().load(deps.storage, id)?;
 
     ensure_eq!(deal.status, DealStatus::Open, ContractError::Unauthorized);
 
@@ -81,31 +196,235 @@ pub fn execute_deal(
         ContractError::DealExpired
     );
 
-    let payment = must_pay(&info, &deal.ask.denom)?;
+    ensure!(
+        deal.allowed_buyers.is_empty() || deal.allowed_buyers.contains(&buyer),
+        ContractError::BuyerNotAllowed
+    );
 
-    ensure_eq!(
-        payment,
-        deal.ask.amount,
-        ContractError::InsufficientAmount(deal.ask.amount.to_string())
+    for ask in &deal.ask {
+        let paid = payment
+            .iter()
+            .find(|p| p.denom == ask.denom)
+            .ok_or_else(|| PaymentError::MissingDenom(ask.denom.to_string()))?;
+        ensure!(
+            deal.allow_partial || paid.amount == ask.amount,
+            ContractError::InsufficientAmount(display_assets(&deal.ask))
+        );
+    }
+
+    if let Some(extra) = payment
+        .iter()
+        .find(|p| !deal.ask.iter().any(|a| a.denom == p.denom))
+    {
+        return Err(PaymentError::ExtraDenom(extra.denom.to_string()).into());
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let previous = deal.clone();
+    let (mut msgs, mut attributes) = match &deal.offer {
+        Offer::Fungible(_) if deal.allow_partial => {
+            let bought = fill_partial(&mut deal, &payment[0])?;
+            if deal.filled_ask == deal.ask[0].amount {
+                deal.status = DealStatus::Claimable;
+            }
+            add_volume(deps.storage, &OFFER_VOLUME, slice::from_ref(&bought))?;
+            record_trade(deps.storage, &env, id, slice::from_ref(&bought), &payment)?;
+            settle(&config, &[bought], config.offer_fee_bps, &buyer, "offer")?
+        }
+        Offer::Fungible(assets) => {
+            deal.status = DealStatus::Claimable;
+            add_volume(deps.storage, &OFFER_VOLUME, assets)?;
+            record_trade(deps.storage, &env, id, assets, &payment)?;
+            settle(&config, assets, config.offer_fee_bps, &buyer, "offer")?
+        }
+        // Nfts can't be split so no fee is taken on them
+        Offer::Nft(_) => {
+            deal.status = DealStatus::Claimable;
+            (deal.offer.transfer_msgs(&buyer)?, vec![])
+        }
+    };
+    add_volume(deps.storage, &ASK_VOLUME, &payment)?;
+
+    if deal.auto_settle {
+        let (ask_msgs, ask_attributes) =
+            settle(&config, &payment, config.ask_fee_bps, &deal.seller, "ask")?;
+        msgs.extend(ask_msgs);
+        attributes.extend(ask_attributes);
+        deal.claimed_ask = deal.filled_ask;
+        if deal.status == DealStatus::Claimable {
+            deal.status = DealStatus::Closed;
+        }
+    }
+
+    deal.buyer = Some(buyer.clone());
+    deals().save(deps.storage, id, &deal)?;
+    BUYER_DEALS.save(deps.storage, (&buyer, id), &Empty {})?;
+    count_deal_status(
+        deps.storage,
+        &deal.seller,
+        Some(&DealStatus::Open),
+        &deal.status,
+    )?;
+    update_balances(deps.storage, Some(&previous), &deal)?;
+
+    let mut event = Event::new("OtcWasm.v1.MsgExecuteDeal")
+        .add_attribute("buyer", buyer)
+        .add_attribute("id", id.to_string())
+        .add_attributes(attributes);
+
+    if deal.allow_partial {
+        event = event
+            .add_attribute("filled_offer", deal.filled_offer)
+            .add_attribute("filled_ask", deal.filled_ask);
+    }
+
+    Ok(Response::new().add_event(event).add_messages(msgs))
+}
+
+/// Records a partial payment on the deal and returns the pro-rata slice of the offer
+/// bought with it, rounding down so the contract never pays out more than it holds
+fn fill_partial(deal: &mut Deal, paid: &Asset) -> Result<Asset, ContractError> {
+    let Offer::Fungible(offer) = &deal.offer else {
+        return Err(ContractError::PartialFillNotSupported);
+    };
+    let (offer, ask) = (&offer[0], &deal.ask[0]);
+
+    let remaining = ask.amount - deal.filled_ask;
+    ensure!(
+        paid.amount <= remaining,
+        ContractError::ExceedsRemaining(
+            Asset {
+                denom: ask.denom.clone(),
+                amount: remaining,
+            }
+            .to_string()
+        )
     );
 
-    deals().update(deps.storage, id, |d| -> Result<Deal, ContractError> {
-        let mut deal = d.unwrap();
-        deal.status = DealStatus::Claimable;
-        deal.buyer = Some(info.sender.clone());
-        Ok(deal)
-    })?;
+    // The last fill takes whatever is left so no dust stays locked
+    let amount = if paid.amount == remaining {
+        offer.amount - deal.filled_offer
+    } else {
+        offer.amount.multiply_ratio(paid.amount, ask.amount)
+    };
+    ensure!(!amount.is_zero(), ContractError::FillTooSmall);
+
+    let bought = Asset {
+        denom: offer.denom.clone(),
+        amount,
+    };
+
+    deal.filled_offer += amount;
+    deal.filled_ask += paid.amount;
+
+    Ok(bought)
+}
+
+/// Records the fill of a deal in the trade history of its pair, baskets have no pair
+fn record_trade(
+    storage: &mut dyn Storage,
+    env: &Env,
+    deal_id: Id,
+    bought: &[Asset],
+    paid: &[Asset],
+) -> StdResult<()> {
+    let ([offer], [ask]) = (bought, paid) else {
+        return Ok(());
+    };
+    let id = next_trade_id(storage)?;
+    let trade = Trade {
+        id,
+        deal_id,
+        offer: offer.clone(),
+        ask: ask.clone(),
+        time: env.block.time,
+    };
+    trades().save(storage, id, &trade)
+}
+
+/// Returns the messages that give the seller back what is left in a deal that
+/// won't be filled anymore, including unclaimed proceeds of partial fills
+fn refund_msgs(config: &Config, deal: &mut Deal) -> StdResult<(Vec<CosmosMsg>, Vec<Attribute>)> {
+    if let Offer::Nft(_) = deal.offer {
+        return Ok((deal.offer.transfer_msgs(&deal.seller)?, vec![]));
+    }
+
+    let (remaining, proceeds) = unsettled_assets(deal);
+    let mut msgs = transfer_msgs(&remaining, &deal.seller)?;
+    let mut attributes = vec![];
+    if let Some(proceeds) = proceeds {
+        let (proceeds_msgs, proceeds_attributes) =
+            settle(config, &[proceeds], config.ask_fee_bps, &deal.seller, "ask")?;
+        msgs.extend(proceeds_msgs);
+        attributes = proceeds_attributes;
+    }
+
+    Ok((msgs, attributes))
+}
+
+/// Returns the fungible offer still escrowed in the deal and the proceeds of partial
+/// fills the seller hasn't claimed yet, which are marked as claimed
+fn unsettled_assets(deal: &mut Deal) -> (Vec<Asset>, Option<Asset>) {
+    let Offer::Fungible(offer) = &deal.offer else {
+        return (vec![], None);
+    };
+    if !deal.allow_partial {
+        return (offer.clone(), None);
+    }
 
-    let msg = CosmosMsg::Bank(cosmwasm_std::BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: vec![deal.offer],
+    let remaining = Asset {
+        denom: offer[0].denom.clone(),
+        amount: offer[0].amount - deal.filled_offer,
+    };
+    let unclaimed = deal.filled_ask - deal.claimed_ask;
+    deal.claimed_ask = deal.filled_ask;
+    let proceeds = (!unclaimed.is_zero()).then(|| Asset {
+        denom: deal.ask[0].denom.clone(),
+        amount: unclaimed,
     });
 
-    let event = Event::new("OtcWasm.v1.MsgExecuteDeal")
-        .add_attribute("buyer", info.sender)
-        .add_attribute("id", id.to_string());
+    (vec![remaining], proceeds)
+}
 
-    Ok(Response::new().add_event(event).add_message(msg))
+/// Splits `assets` into the protocol fee, rounded down, and the net amount
+fn split_fee(assets: &[Asset], fee_bps: u16) -> (Vec<Asset>, Vec<Asset>) {
+    let mut fee = vec![];
+    let mut net = vec![];
+    for asset in assets {
+        let amount = asset.amount.multiply_ratio(fee_bps, BPS_DENOMINATOR);
+        fee.push(Asset {
+            denom: asset.denom.clone(),
+            amount,
+        });
+        net.push(Asset {
+            denom: asset.denom.clone(),
+            amount: asset.amount - amount,
+        });
+    }
+    (fee, net)
+}
+
+/// Returns the messages paying `assets` to `recipient` minus the protocol fee, which
+/// goes to the fee collector, along with the attributes to reconcile the amounts
+fn settle(
+    config: &Config,
+    assets: &[Asset],
+    fee_bps: u16,
+    recipient: &Addr,
+    side: &str,
+) -> StdResult<(Vec<CosmosMsg>, Vec<Attribute>)> {
+    let (fee, net) = split_fee(assets, fee_bps);
+
+    let mut msgs = transfer_msgs(&net, recipient)?;
+    msgs.extend(transfer_msgs(&fee, &config.fee_collector)?);
+
+    let attributes = vec![
+        attr(format!("{side}_gross"), display_assets(assets)),
+        attr(format!("{side}_fee"), display_assets(&fee)),
+        attr(format!("{side}_net"), display_assets(&net)),
+    ];
+
+    Ok((msgs, attributes))
 }
 
 pub fn create_deal(
@@ -114,109 +433,444 @@ pub fn create_deal(
     info: MessageInfo,
     msg: CreateDealMsg,
 ) -> Result<Response, ContractError> {
-    let offer = one_coin(&info)?;
-
-    validate_native_denom(msg.ask.denom.clone())?;
+    ensure!(!info.funds.is_empty(), PaymentError::NoFunds {});
 
-    let config = CONFIG.load(deps.storage)?;
+    let offer = native_assets(&info.funds);
 
     ensure!(
-        config.duration_range.contains(&msg.duration),
-        ContractError::InvalidDuration(
-            config.duration_range[0],
-            config.duration_range[config.duration_range.len() - 1]
-        )
+        native_assets(&msg.offer) == offer,
+        ContractError::InvalidOffer
     );
 
+    save_deal(deps, env, info.sender, Offer::Fungible(offer), msg)
+}
+
+fn save_deal(
+    deps: DepsMut,
+    env: Env,
+    seller: Addr,
+    offer: Offer,
+    msg: CreateDealMsg,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref())?;
+
+    let ask = validate_ask(deps.as_ref(), msg.ask)?;
+    match &offer {
+        Offer::Fungible(assets) => ensure_tradable(deps.as_ref(), assets)?,
+        // Collections are listed by address like cw20 tokens
+        Offer::Nft(nft) => ensure_listed(deps.as_ref(), nft.collection.as_str())?,
+    }
+    ensure_tradable(deps.as_ref(), &ask)?;
+
+    if msg.allow_partial {
+        ensure!(
+            matches!(&offer, Offer::Fungible(o) if o.len() == 1) && ask.len() == 1,
+            ContractError::PartialFillNotSupported
+        );
+    }
+
+    // The duration is checked before it's added to the block time, the policy caps it
+    // so the addition can't overflow
+    let end_time = match msg.end_time {
+        Some(end_time) => {
+            validate_duration(
+                deps.as_ref(),
+                end_time.seconds().saturating_sub(env.block.time.seconds()),
+            )?;
+            end_time
+        }
+        None => {
+            validate_duration(deps.as_ref(), msg.duration)?;
+            env.block.time.plus_seconds(msg.duration)
+        }
+    };
+
+    let mut allowed_buyers = msg
+        .allowed_buyers
+        .unwrap_or_default()
+        .iter()
+        .map(|b| deps.api.addr_validate(b))
+        .collect::<StdResult<Vec<_>>>()?;
+    allowed_buyers.sort();
+    allowed_buyers.dedup();
+
     let id = next_id(deps.storage)?;
 
+    for buyer in &allowed_buyers {
+        COUNTERPARTY_DEALS.save(deps.storage, (buyer, id), &Empty {})?;
+    }
+
     let deal = Deal {
         id,
         offer,
-        seller: info.sender.clone(),
+        seller: seller.clone(),
         buyer: None,
-        ask: msg.ask,
+        ask,
         status: DealStatus::Open,
         creation_time: env.block.time,
-        end_time: env.block.time.plus_seconds(msg.duration),
+        end_time,
+        allow_partial: msg.allow_partial,
+        auto_settle: msg.auto_settle,
+        filled_offer: Uint128::zero(),
+        filled_ask: Uint128::zero(),
+        claimed_ask: Uint128::zero(),
+        allowed_buyers,
     };
 
     deals().save(deps.storage, id, &deal)?;
+    count_deal_status(deps.storage, &seller, None, &deal.status)?;
+    update_balances(deps.storage, None, &deal)?;
 
     let event = Event::new("OtcWasm.v1.MsgCreateDeal")
-        .add_attribute("seller", info.sender.to_string())
+        .add_attribute("seller", seller.to_string())
         .add_attribute("id", id.to_string());
 
     Ok(Response::new().add_event(event))
 }
 
+fn validate_duration(deps: Deps, duration: u64) -> Result<(), ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    config.duration_policy.check(duration)
+}
+
+fn validate_ask(deps: Deps, ask: Vec<UncheckedAsset>) -> Result<Vec<Asset>, ContractError> {
+    ensure!(
+        !ask.is_empty(),
+        ContractError::InvalidAsk("ask can't be empty".to_string())
+    );
+
+    let mut assets = ask
+        .into_iter()
+        .map(|a| a.into_checked(deps))
+        .collect::<Result<Vec<_>, _>>()?;
+    assets.sort_by_key(|a| a.denom.to_string());
+
+    ensure!(
+        assets.iter().all(|a| !a.amount.is_zero()),
+        ContractError::InvalidAsk("amounts must be greater than zero".to_string())
+    );
+    ensure!(
+        assets.windows(2).all(|w| w[0].denom != w[1].denom),
+        ContractError::InvalidAsk("denoms must be unique".to_string())
+    );
+    ensure!(
+        assets.len() == 1
+            || assets
+                .iter()
+                .all(|a| matches!(a.denom, CheckedDenom::Native(_))),
+        ContractError::InvalidAsk("a cw20 can't be combined with other assets".to_string())
+    );
+
+    Ok(assets)
+}
+
 pub fn claim(deps: DepsMut, info: MessageInfo, id: Id) -> Result<Response, ContractError> {
-    let deal = deals().load(deps.storage, id)?;
+    let mut deal = deals().load(deps.storage, id)?;
     ensure_eq!(info.sender, deal.seller, ContractError::Unauthorized);
-    ensure_eq!(
-        deal.status,
-        DealStatus::Claimable,
-        ContractError::Unauthorized
-    );
-    let msg = BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: vec![deal.ask],
+
+    let previous = deal.clone();
+    let proceeds = if deal.allow_partial {
+        // Proceeds of partial fills can be claimed while the deal is still open
+        ensure!(
+            matches!(deal.status, DealStatus::Open | DealStatus::Claimable),
+            ContractError::Unauthorized
+        );
+        let amount = deal.filled_ask - deal.claimed_ask;
+        ensure!(!amount.is_zero(), ContractError::Unauthorized);
+        deal.claimed_ask = deal.filled_ask;
+        vec![Asset {
+            denom: deal.ask[0].denom.clone(),
+            amount,
+        }]
+    } else {
+        ensure_eq!(
+            deal.status,
+            DealStatus::Claimable,
+            ContractError::Unauthorized
+        );
+        deal.ask.clone()
     };
+    let config = CONFIG.load(deps.storage)?;
+    let (msgs, attributes) = settle(&config, &proceeds, config.ask_fee_bps, &info.sender, "ask")?;
 
-    deals().update(deps.storage, id, |d| -> Result<Deal, ContractError> {
-        let mut deal = d.unwrap();
+    if deal.status == DealStatus::Claimable {
         deal.status = DealStatus::Closed;
-        Ok(deal)
-    })?;
+        count_deal_status(
+            deps.storage,
+            &deal.seller,
+            Some(&DealStatus::Claimable),
+            &deal.status,
+        )?;
+    }
+    deals().save(deps.storage, id, &deal)?;
+    update_balances(deps.storage, Some(&previous), &deal)?;
 
     let event = Event::new("OtcWasm.v1.MsgClaim")
         .add_attribute("claimer", info.sender)
+        .add_attribute("id", id.to_string())
+        .add_attributes(attributes);
+
+    Ok(Response::new().add_event(event).add_messages(msgs))
+}
+
+pub fn cancel_deal(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Id,
+) -> Result<Response, ContractError> {
+    let mut deal = deals()
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::DealNotFound)?;
+    ensure_eq!(info.sender, deal.seller, ContractError::Unauthorized);
+    ensure_eq!(deal.status, DealStatus::Open, ContractError::Unauthorized);
+    ensure!(
+        !Expiration::AtTime(deal.end_time).is_expired(&env.block),
+        ContractError::DealExpired
+    );
+
+    let config = CONFIG.load(deps.storage)?;
+    let previous = deal.clone();
+    let (msgs, attributes) = refund_msgs(&config, &mut deal)?;
+    deal.status = DealStatus::Cancelled;
+    deals().save(deps.storage, id, &deal)?;
+    count_deal_status(
+        deps.storage,
+        &deal.seller,
+        Some(&DealStatus::Open),
+        &deal.status,
+    )?;
+    update_balances(deps.storage, Some(&previous), &deal)?;
+
+    let event = Event::new("OtcWasm.v1.MsgCancelDeal")
+        .add_attribute("id", id.to_string())
+        .add_attributes(attributes);
+    Ok(Response::new().add_event(event).add_messages(msgs))
+}
+
+pub fn create_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: CreateBidMsg,
+) -> Result<Response, ContractError> {
+    let offer = one_coin(&info)?;
+
+    save_bid(deps, env, info.sender, offer.into(), msg)
+}
+
+fn save_bid(
+    deps: DepsMut,
+    env: Env,
+    buyer: Addr,
+    offer: Asset,
+    msg: CreateBidMsg,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref())?;
+
+    let ask = msg.ask.into_checked(deps.as_ref())?;
+
+    ensure!(
+        !ask.amount.is_zero(),
+        ContractError::InvalidAsk("amounts must be greater than zero".to_string())
+    );
+    ensure_tradable(deps.as_ref(), &[offer.clone(), ask.clone()])?;
+
+    validate_duration(deps.as_ref(), msg.duration)?;
+
+    let allowed_seller = msg
+        .allowed_seller
+        .map(|s| deps.api.addr_validate(&s))
+        .transpose()?;
+
+    let id = next_bid_id(deps.storage)?;
+
+    let bid = Bid {
+        id,
+        buyer: buyer.clone(),
+        seller: None,
+        offer,
+        ask,
+        status: DealStatus::Open,
+        creation_time: env.block.time,
+        end_time: env.block.time.plus_seconds(msg.duration),
+        allowed_seller,
+    };
+
+    bids().save(deps.storage, id, &bid)?;
+
+    let event = Event::new("OtcWasm.v1.MsgCreateBid")
+        .add_attribute("buyer", buyer.to_string())
         .add_attribute("id", id.to_string());
 
+    Ok(Response::new().add_event(event))
+}
+
+pub fn execute_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Id,
+) -> Result<Response, ContractError> {
+    let payment = one_coin(&info)?;
+
+    fill_bid(deps, env, info.sender, id, payment.into())
+}
+
+fn fill_bid(
+    deps: DepsMut,
+    env: Env,
+    seller: Addr,
+    id: Id,
+    payment: Asset,
+) -> Result<Response, ContractError> {
+    ensure_not_paused(deps.as_ref())?;
+
+    let mut bid = bids().load(deps.storage, id)?;
+
+    ensure_eq!(bid.status, DealStatus::Open, ContractError::Unauthorized);
+
+    ensure!(
+        !Expiration::AtTime(bid.end_time).is_expired(&env.block),
+        ContractError::BidExpired
+    );
+
+    ensure!(
+        bid.allowed_seller.as_ref().is_none_or(|s| *s == seller),
+        ContractError::SellerNotAllowed
+    );
+
+    ensure_eq!(
+        payment.denom,
+        bid.ask.denom,
+        PaymentError::MissingDenom(bid.ask.denom.to_string())
+    );
+
+    ensure_eq!(
+        payment.amount,
+        bid.ask.amount,
+        ContractError::InsufficientAmount(bid.ask.to_string())
+    );
+
+    bid.status = DealStatus::Claimable;
+    bid.seller = Some(seller.clone());
+    bids().save(deps.storage, id, &bid)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let (msgs, attributes) = settle(
+        &config,
+        &[bid.offer],
+        config.offer_fee_bps,
+        &seller,
+        "offer",
+    )?;
+
+    let event = Event::new("OtcWasm.v1.MsgExecuteBid")
+        .add_attribute("seller", seller)
+        .add_attribute("id", id.to_string())
+        .add_attributes(attributes);
+
+    Ok(Response::new().add_event(event).add_messages(msgs))
+}
+
+pub fn claim_bid(deps: DepsMut, info: MessageInfo, id: Id) -> Result<Response, ContractError> {
+    let mut bid = bids().load(deps.storage, id)?;
+    ensure_eq!(info.sender, bid.buyer, ContractError::Unauthorized);
+    ensure_eq!(
+        bid.status,
+        DealStatus::Claimable,
+        ContractError::Unauthorized
+    );
+
+    bid.status = DealStatus::Closed;
+    bids().save(deps.storage, id, &bid)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let (msgs, attributes) = settle(&config, &[bid.ask], config.ask_fee_bps, &info.sender, "ask")?;
+
+    let event = Event::new("OtcWasm.v1.MsgClaimBid")
+        .add_attribute("claimer", info.sender)
+        .add_attribute("id", id.to_string())
+        .add_attributes(attributes);
+
+    Ok(Response::new().add_event(event).add_messages(msgs))
+}
+
+pub fn cancel_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Id,
+) -> Result<Response, ContractError> {
+    let bid = bids().update(deps.storage, id, |b| -> Result<Bid, ContractError> {
+        let mut bid = b.ok_or(ContractError::BidNotFound)?;
+        ensure_eq!(info.sender, bid.buyer, ContractError::Unauthorized);
+        ensure_eq!(bid.status, DealStatus::Open, ContractError::Unauthorized);
+        ensure!(
+            !Expiration::AtTime(bid.end_time).is_expired(&env.block),
+            ContractError::BidExpired
+        );
+        bid.status = DealStatus::Cancelled;
+        Ok(bid)
+    })?;
+
+    let msg = bid.offer.transfer_msg(&info.sender)?;
+
+    let event = Event::new("OtcWasm.v1.MsgCancelBid").add_attribute("id", id.to_string());
     Ok(Response::new().add_event(event).add_message(msg))
 }
 
-pub fn cancel_deal(
+pub fn withdraw_bid(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     id: Id,
 ) -> Result<Response, ContractError> {
-    let deal = deals().update(deps.storage, id, |d| -> Result<Deal, ContractError> {
-        let mut deal = d.ok_or(ContractError::DealNotFound)?;
-        ensure_eq!(info.sender, deal.seller, ContractError::Unauthorized);
-        ensure_eq!(deal.status, DealStatus::Open, ContractError::Unauthorized);
+    let bid = bids().update(deps.storage, id, |b| -> Result<Bid, ContractError> {
+        let mut bid = b.ok_or(ContractError::BidNotFound)?;
+        ensure_eq!(info.sender, bid.buyer, ContractError::Unauthorized);
+        ensure_eq!(bid.status, DealStatus::Open, ContractError::Unauthorized);
         ensure!(
-            !Expiration::AtTime(deal.end_time).is_expired(&env.block),
-            ContractError::DealExpired
+            Expiration::AtTime(bid.end_time).is_expired(&env.block),
+            ContractError::Unauthorized
         );
-        deal.status = DealStatus::Cancelled;
-        Ok(deal)
+        bid.status = DealStatus::Expired;
+        Ok(bid)
     })?;
 
-    let msg = BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: vec![deal.offer],
-    };
+    let msg = bid.offer.transfer_msg(&info.sender)?;
+
+    let event = Event::new("OtcWasm.v1.MsgWithdrawBid")
+        .add_attribute("buyer", info.sender)
+        .add_attribute("id", id.to_string());
 
-    let event = Event::new("OtcWasm.v1.MsgCancelDeal").add_attribute("id", id.to_string());
     Ok(Response::new().add_event(event).add_message(msg))
 }
 
 pub fn update_config(
     deps: DepsMut,
     info: MessageInfo,
-    owner: Option<String>,
-    duration_range: Option<Vec<u64>>,
+    msg: UpdateConfigMsg,
 ) -> Result<Response, ContractError> {
+    OWNERSHIP.load(deps.storage)?.assert_owner(&info.sender)?;
+
     CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
-        ensure_eq!(info.sender, config.owner, ContractError::Unauthorized);
-        if let Some(owner) = owner {
-            config.owner = deps.api.addr_validate(&owner)?;
+        if let Some(duration_policy) = msg.duration_policy {
+            config.duration_policy = duration_policy;
+        }
+        if let Some(fee_collector) = msg.fee_collector {
+            config.fee_collector = deps.api.addr_validate(&fee_collector)?;
+        }
+        if let Some(offer_fee_bps) = msg.offer_fee_bps {
+            config.offer_fee_bps = offer_fee_bps;
         }
-        if let Some(duration_range) = duration_range {
-            config.duration_range = duration_range;
+        if let Some(ask_fee_bps) = msg.ask_fee_bps {
+            config.ask_fee_bps = ask_fee_bps;
         }
+        if let Some(pauser) = msg.pauser {
+            config.pauser = Some(deps.api.addr_validate(&pauser)?);
+        }
+        config.validate()?;
         Ok(config)
     })?;
 
@@ -224,3 +878,295 @@ pub fn update_config(
 
     Ok(Response::new().add_event(event))
 }
+
+pub fn update_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    action: OwnershipAction,
+) -> Result<Response, ContractError> {
+    let mut ownership = OWNERSHIP.load(deps.storage)?;
+
+    let event = match action {
+        OwnershipAction::TransferOwnership { new_owner, expiry } => {
+            ownership.assert_owner(&info.sender)?;
+            ensure!(
+                expiry.is_none_or(|e| !e.is_expired(&env.block)),
+                ContractError::InvalidExpiration
+            );
+            let new_owner = deps.api.addr_validate(&new_owner)?;
+            ownership.pending_owner = Some(new_owner.clone());
+            ownership.pending_expiry = expiry;
+            Event::new("OtcWasm.v1.MsgTransferOwnership")
+                .add_attribute("owner", info.sender)
+                .add_attribute("pending_owner", new_owner)
+        }
+        OwnershipAction::AcceptOwnership => {
+            let pending_owner = ownership
+                .pending_owner
+                .take()
+                .ok_or(ContractError::NoPendingOwner)?;
+            ensure_eq!(info.sender, pending_owner, ContractError::Unauthorized);
+            ensure!(
+                ownership
+                    .pending_expiry
+                    .take()
+                    .is_none_or(|e| !e.is_expired(&env.block)),
+                ContractError::OwnershipTransferExpired
+            );
+            ownership.owner = Some(pending_owner);
+            Event::new("OtcWasm.v1.MsgAcceptOwnership").add_attribute("owner", info.sender)
+        }
+        OwnershipAction::RenounceOwnership => {
+            ownership.assert_owner(&info.sender)?;
+            // Nobody could unpause the contract afterwards, nor remove the pauser
+            ensure_not_paused(deps.as_ref())?;
+            CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+                config.pauser = None;
+                Ok(config)
+            })?;
+            ownership = Ownership {
+                owner: None,
+                pending_owner: None,
+                pending_expiry: None,
+            };
+            Event::new("OtcWasm.v1.MsgRenounceOwnership").add_attribute("owner", info.sender)
+        }
+    };
+
+    OWNERSHIP.save(deps.storage, &ownership)?;
+
+    Ok(Response::new().add_event(event))
+}
+
+pub fn pause(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.pauser.as_ref() != Some(&info.sender) {
+        OWNERSHIP.load(deps.storage)?.assert_owner(&info.sender)?;
+    }
+
+    PAUSED.save(deps.storage, &true)?;
+
+    let event = Event::new("OtcWasm.v1.MsgPause").add_attribute("sender", info.sender);
+    Ok(Response::new().add_event(event))
+}
+
+pub fn unpause(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    OWNERSHIP.load(deps.storage)?.assert_owner(&info.sender)?;
+
+    PAUSED.save(deps.storage, &false)?;
+
+    let event = Event::new("OtcWasm.v1.MsgUnpause").add_attribute("sender", info.sender);
+    Ok(Response::new().add_event(event))
+}
+
+pub fn remove_pauser(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    OWNERSHIP.load(deps.storage)?.assert_owner(&info.sender)?;
+
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.pauser = None;
+        Ok(config)
+    })?;
+
+    let event = Event::new("OtcWasm.v1.MsgRemovePauser");
+    Ok(Response::new().add_event(event))
+}
+
+/// Validates a denom without querying its token contract, returning the string it is
+/// stored under
+fn checked_denom(deps: Deps, denom: UncheckedDenom) -> Result<String, ContractError> {
+    let denom = match denom {
+        UncheckedDenom::Native(denom) => validate_native_denom(denom)?,
+        UncheckedDenom::Cw20(addr) => CheckedDenom::Cw20(deps.api.addr_validate(&addr)?),
+    };
+    Ok(denom.to_string())
+}
+
+fn ensure_not_paused(deps: Deps) -> Result<(), ContractError> {
+    let paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    ensure!(!paused, ContractError::Paused);
+    Ok(())
+}
+
+pub fn update_denoms(
+    deps: DepsMut,
+    info: MessageInfo,
+    list: DenomList,
+    add: Vec<UncheckedDenom>,
+    remove: Vec<UncheckedDenom>,
+) -> Result<Response, ContractError> {
+    OWNERSHIP.load(deps.storage)?.assert_owner(&info.sender)?;
+
+    // Entries are matched against the checked denoms of deals, so they are stored in
+    // the same canonical form
+    let add = add
+        .into_iter()
+        .map(|denom| checked_denom(deps.as_ref(), denom))
+        .collect::<Result<Vec<_>, _>>()?;
+    let remove = remove
+        .into_iter()
+        .map(|denom| checked_denom(deps.as_ref(), denom))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let map = list.map();
+    for denom in &add {
+        map.save(deps.storage, denom, &Empty {})?;
+    }
+    for denom in &remove {
+        map.remove(deps.storage, denom);
+    }
+
+    let mut event =
+        Event::new("OtcWasm.v1.MsgUpdateDenoms").add_attribute("list", list.as_string());
+    if !add.is_empty() {
+        event = event.add_attribute("added", add.join(","));
+    }
+    if !remove.is_empty() {
+        event = event.add_attribute("removed", remove.join(","));
+    }
+
+    Ok(Response::new().add_event(event))
+}
+
+pub fn set_trade_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    denom: UncheckedDenom,
+    limit: Option<TradeLimit>,
+) -> Result<Response, ContractError> {
+    OWNERSHIP.load(deps.storage)?.assert_owner(&info.sender)?;
+
+    let denom = checked_denom(deps.as_ref(), denom)?;
+
+    let mut event = Event::new("OtcWasm.v1.MsgSetTradeLimit").add_attribute("denom", &denom);
+    match limit {
+        Some(limit) => {
+            if let (Some(min), Some(max)) = (limit.min_amount, limit.max_amount) {
+                ensure!(min <= max, ContractError::InvalidTradeLimit);
+            }
+            if let Some(min) = limit.min_amount {
+                event = event.add_attribute("min_amount", min);
+            }
+            if let Some(max) = limit.max_amount {
+                event = event.add_attribute("max_amount", max);
+            }
+            TRADE_LIMITS.save(deps.storage, &denom, &limit)?;
+        }
+        None => TRADE_LIMITS.remove(deps.storage, &denom),
+    }
+
+    Ok(Response::new().add_event(event))
+}
+
+/// Rejects assets whose denom is denied, or missing from a non empty allowlist,
+/// and amounts outside the trade limits of their denom
+/// Whether the denom passes the denylist and, once it has entries, the allowlist
+fn ensure_listed(deps: Deps, denom: &str) -> Result<(), ContractError> {
+    let allowlist = !ALLOWED_DENOMS.is_empty(deps.storage);
+    ensure!(
+        !DENIED_DENOMS.has(deps.storage, denom)
+            && (!allowlist || ALLOWED_DENOMS.has(deps.storage, denom)),
+        ContractError::DenomNotAllowed(denom.to_string())
+    );
+    Ok(())
+}
+
+fn ensure_tradable(deps: Deps, assets: &[Asset]) -> Result<(), ContractError> {
+    for asset in assets {
+        let denom = asset.denom.to_string();
+        ensure_listed(deps, &denom)?;
+
+        let Some(limit) = TRADE_LIMITS.may_load(deps.storage, &denom)? else {
+            continue;
+        };
+        let bound = |amount| {
+            Asset {
+                denom: asset.denom.clone(),
+                amount,
+            }
+            .to_string()
+        };
+        if let Some(min) = limit.min_amount {
+            ensure!(
+                asset.amount >= min,
+                ContractError::AmountBelowMinimum(asset.to_string(), bound(min))
+            );
+        }
+        if let Some(max) = limit.max_amount {
+            ensure!(
+                asset.amount <= max,
+                ContractError::AmountAboveMaximum(asset.to_string(), bound(max))
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn process_expired(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+
+    let limit = limit
+        .unwrap_or(DEFAULT_PROCESS_LIMIT)
+        .min(MAX_PROCESS_LIMIT);
+    // Only open deals holding native coins are walked, so closed deals that ended earlier
+    // don't add to the cost and a token contract failing its transfers can't block the crank
+    let max = Bound::InclusiveRaw((env.block.time.seconds(), Id::MAX).joined_key());
+    let expired = deals()
+        .idx
+        .refundable
+        .sub_prefix(DealStatus::Open.as_string())
+        .range(deps.storage, None, Some(max), Order::Ascending)
+        .take(limit as usize)
+        .map(|item| item.map(|(_, deal)| deal))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let config = CONFIG.load(deps.storage)?;
+    // Refunds are grouped so each seller gets a single transfer per denom
+    let mut payouts: BTreeMap<Addr, Vec<Asset>> = BTreeMap::new();
+    let mut msgs = vec![];
+    let mut ids = vec![];
+    for mut deal in expired {
+        let previous = deal.clone();
+        let (remaining, proceeds) = unsettled_assets(&mut deal);
+        payouts
+            .entry(deal.seller.clone())
+            .or_default()
+            .extend(remaining);
+        if let Some(proceeds) = proceeds {
+            let (fee, net) = split_fee(&[proceeds], config.ask_fee_bps);
+            payouts.entry(deal.seller.clone()).or_default().extend(net);
+            payouts
+                .entry(config.fee_collector.clone())
+                .or_default()
+                .extend(fee);
+        }
+
+        deal.status = DealStatus::Expired;
+        deals().save(deps.storage, deal.id, &deal)?;
+        count_deal_status(
+            deps.storage,
+            &deal.seller,
+            Some(&DealStatus::Open),
+            &deal.status,
+        )?;
+        update_balances(deps.storage, Some(&previous), &deal)?;
+        ids.push(deal.id.to_string());
+    }
+
+    for (recipient, assets) in payouts {
+        msgs.extend(transfer_msgs(&assets, &recipient)?);
+    }
+
+    let mut event = Event::new("OtcWasm.v1.MsgProcessExpired")
+        .add_attribute("processed", ids.len().to_string());
+    if !ids.is_empty() {
+        event = event.add_attribute("ids", ids.join(","));
+    }
+
+    Ok(Response::new().add_event(event).add_messages(msgs))
+}
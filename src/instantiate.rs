@@ -3,12 +3,12 @@ use cosmwasm_std::entry_point;
 
 use crate::error::ContractError;
 use crate::msg::InstantiateMsg;
-use crate::state::{Config, CONFIG};
+use crate::state::{Config, Ownership, CONFIG, OWNERSHIP};
 use cosmwasm_std::{DepsMut, Env, Event, MessageInfo, Response};
 use cw2::set_contract_version;
 
-const CONTRACT_NAME: &str = "crates.io:otc-wasm";
-const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const CONTRACT_NAME: &str = "crates.io:otc-wasm";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -20,11 +20,23 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     let config = Config {
-        owner: deps.api.addr_validate(&msg.owner)?,
-        duration_range: msg.duration_range,
+        duration_policy: msg.duration_policy,
+        fee_collector: deps.api.addr_validate(&msg.fee_collector)?,
+        offer_fee_bps: msg.offer_fee_bps,
+        ask_fee_bps: msg.ask_fee_bps,
+        pauser: msg.pauser.map(|p| deps.api.addr_validate(&p)).transpose()?,
     };
+    config.validate()?;
 
     CONFIG.save(deps.storage, &config)?;
+    OWNERSHIP.save(
+        deps.storage,
+        &Ownership {
+            owner: Some(deps.api.addr_validate(&msg.owner)?),
+            pending_owner: None,
+            pending_expiry: None,
+        },
+    )?;
 
     let event = Event::new("OtcWasm.v1.MsgInstantiateContract");
     Ok(Response::new().add_event(event))
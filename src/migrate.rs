@@ -1,11 +1,241 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
-use crate::{error::ContractError, msg::MigrateMsg};
-use cosmwasm_std::{DepsMut, Env, Event, Response};
+use crate::{
+    error::ContractError,
+    instantiate::{CONTRACT_NAME, CONTRACT_VERSION},
+    msg::MigrateMsg,
+    state::{
+        add_volume, count_deal_status, deals, update_balances, Config, Deal, DurationPolicy,
+        Ownership, ASK_VOLUME, BUYER_DEALS, CONFIG, ID_COUNT, OFFER_VOLUME, OWNERSHIP,
+    },
+};
+use cosmwasm_std::{
+    DepsMut, Empty, Env, Event, MessageInfo, Order, Response, StdResult, Storage, Uint128,
+};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bound, IndexList, PrimaryKey};
+use cw_utils::nonpayable;
+use semver::Version;
+
+const DEFAULT_MIGRATE_LIMIT: u32 = 30;
+const MAX_MIGRATE_LIMIT: u32 = 100;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    let event = Event::new("OtcWasm.v1.MsgMigrateContract");
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigration(stored.contract));
+    }
+
+    let previous = parse_version(&stored.version)?;
+    let current = parse_version(CONTRACT_VERSION)?;
+    if previous > current {
+        return Err(ContractError::CannotDowngrade(
+            stored.version,
+            CONTRACT_VERSION.to_string(),
+        ));
+    }
+
+    if previous < Version::new(0, 2, 0) {
+        v0_1::upgrade(deps.storage)?;
+        v0_1::upgrade_deals(deps.storage, deal_limit(msg.deal_limit))?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let event = Event::new("OtcWasm.v1.MsgMigrateContract")
+        .add_attribute("previous_version", previous.to_string())
+        .add_attribute("version", CONTRACT_VERSION)
+        .add_attribute(
+            "pending_deals",
+            v0_1::BACKFILL.exists(deps.storage).to_string(),
+        );
     Ok(Response::new().add_event(event))
 }
+
+/// Upgrades the next `limit` deals a migration left in the 0.1 layout
+pub fn migrate_deals(
+    deps: DepsMut,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    nonpayable(&info)?;
+
+    let upgraded = v0_1::upgrade_deals(deps.storage, deal_limit(limit))?;
+
+    let event = Event::new("OtcWasm.v1.MsgMigrateDeals")
+        .add_attribute("upgraded", upgraded.to_string())
+        .add_attribute(
+            "pending_deals",
+            v0_1::BACKFILL.exists(deps.storage).to_string(),
+        );
+    Ok(Response::new().add_event(event))
+}
+
+fn deal_limit(limit: Option<u32>) -> u32 {
+    limit
+        .unwrap_or(DEFAULT_MIGRATE_LIMIT)
+        .min(MAX_MIGRATE_LIMIT)
+}
+
+fn parse_version(version: &str) -> Result<Version, ContractError> {
+    version
+        .parse()
+        .map_err(|_| ContractError::InvalidVersion(version.to_string()))
+}
+
+/// State layout of the 0.1 releases, which only supported single coin deals
+pub mod v0_1 {
+    use super::*;
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, Coin, Timestamp};
+    use cw_storage_plus::{Item, Map};
+
+    use crate::state::{DealStatus, Id, Offer};
+
+    #[cw_serde]
+    pub struct Config {
+        pub owner: Addr,
+        pub duration_range: Vec<u64>,
+    }
+
+    #[cw_serde]
+    pub struct Deal {
+        pub id: Id,
+        pub seller: Addr,
+        pub buyer: Option<Addr>,
+        pub offer: Coin,
+        pub ask: Coin,
+        pub status: DealStatus,
+        pub creation_time: Timestamp,
+        pub end_time: Timestamp,
+    }
+
+    pub const CONFIG: Item<Config> = Item::new("config");
+    pub const DEALS: Map<Id, Deal> = Map::new("deals");
+
+    /// Range of deal ids still stored in the 0.1 layout, removed once all are upgraded
+    #[cw_serde]
+    pub struct Backfill {
+        pub next: Id,
+        pub end: Id,
+    }
+
+    pub const BACKFILL: Item<Backfill> = Item::new("v0_1_backfill");
+
+    /// Upgrades the config and ownership, the deals are left for [`upgrade_deals`] to
+    /// backfill in pages so the migration fits in a block whatever their number
+    pub fn upgrade(storage: &mut dyn Storage) -> Result<(), ContractError> {
+        let config = CONFIG.load(storage)?;
+        // 0.1 never checked its durations, an empty or zero one has to be fixed before
+        // migrating or no deal could be created afterwards
+        let duration_policy = DurationPolicy::Discrete(config.duration_range);
+        duration_policy.validate()?;
+        super::CONFIG.save(
+            storage,
+            &super::Config {
+                fee_collector: config.owner.clone(),
+                duration_policy,
+                offer_fee_bps: 0,
+                ask_fee_bps: 0,
+                pauser: None,
+            },
+        )?;
+        OWNERSHIP.save(
+            storage,
+            &Ownership {
+                owner: Some(config.owner),
+                pending_owner: None,
+                pending_expiry: None,
+            },
+        )?;
+
+        let first = DEALS.keys(storage, None, None, Order::Ascending).next();
+        let last = DEALS.keys(storage, None, None, Order::Descending).next();
+        if let (Some(first), Some(last)) = (first, last) {
+            let end = last? + 1;
+            BACKFILL.save(storage, &Backfill { next: first?, end })?;
+            // New deals must be stored past the legacy ones, which are read in the old
+            // layout until upgraded
+            if ID_COUNT.may_load(storage)?.unwrap_or(1) < end {
+                ID_COUNT.save(storage, &end)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Backfills the fields added since 0.1 on the next `limit` legacy deals, rebuilds
+    /// their indexes and counts them, their traded volume and the balances of their
+    /// sellers. Legacy deals can't be used until upgraded. Returns the number upgraded
+    pub fn upgrade_deals(storage: &mut dyn Storage, limit: u32) -> StdResult<u32> {
+        let Some(backfill) = BACKFILL.may_load(storage)? else {
+            return Ok(0);
+        };
+
+        let legacy = DEALS
+            .range(
+                storage,
+                Some(Bound::inclusive(backfill.next)),
+                Some(Bound::exclusive(backfill.end)),
+                Order::Ascending,
+            )
+            .take(limit as usize)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        match legacy.last() {
+            Some((id, _)) if legacy.len() == limit as usize && id + 1 < backfill.end => {
+                BACKFILL.save(
+                    storage,
+                    &Backfill {
+                        next: id + 1,
+                        end: backfill.end,
+                    },
+                )?;
+            }
+            _ => BACKFILL.remove(storage),
+        }
+
+        let upgraded = legacy.len() as u32;
+        let deals = deals();
+        for (id, deal) in legacy {
+            let deal = super::Deal {
+                id: deal.id,
+                seller: deal.seller,
+                buyer: deal.buyer,
+                offer: deal.offer.into(),
+                ask: vec![deal.ask.into()],
+                status: deal.status,
+                creation_time: deal.creation_time,
+                end_time: deal.end_time,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            };
+            // The old entries can't be read back as the new layout, so the index
+            // entries are dropped by hand before writing the deal again
+            let pk = id.joined_key();
+            for index in deals.idx.get_indexes() {
+                index.remove(storage, &pk, &deal)?;
+            }
+            deals.replace(storage, id, Some(&deal), None)?;
+            if let Some(buyer) = &deal.buyer {
+                BUYER_DEALS.save(storage, (buyer, id), &Empty {})?;
+            }
+            count_deal_status(storage, &deal.seller, None, &deal.status)?;
+            update_balances(storage, None, &deal)?;
+            if matches!(deal.status, DealStatus::Claimable | DealStatus::Closed) {
+                if let Offer::Fungible(offer) = &deal.offer {
+                    add_volume(storage, &OFFER_VOLUME, offer)?;
+                }
+                add_volume(storage, &ASK_VOLUME, &deal.ask)?;
+            }
+        }
+
+        Ok(upgraded)
+    }
+}
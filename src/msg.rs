@@ -1,16 +1,29 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Coin;
+use cosmwasm_std::{Binary, Coin, Decimal, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_denom::UncheckedDenom;
+use cw_utils::Expiration;
 
-use crate::state::{Config, Deal, DealStatus, Id};
+use crate::state::{
+    Bid, Config, Deal, DealStatus, DenomList, DurationPolicy, Id, Ownership, Stats, Trade,
+    TradeLimit, UncheckedAsset,
+};
 
 #[cw_serde]
 pub struct InstantiateMsg {
     pub owner: String,
-    pub duration_range: Vec<u64>,
+    pub duration_policy: DurationPolicy,
+    pub fee_collector: String,
+    pub offer_fee_bps: u16,
+    pub ask_fee_bps: u16,
+    pub pauser: Option<String>,
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// Deals upgraded by the migration itself, the rest are left for `MigrateDeals`
+    pub deal_limit: Option<u32>,
+}
 
 #[cw_serde]
 pub enum ExecuteMsg {
@@ -19,10 +32,78 @@ pub enum ExecuteMsg {
     CreateDeal(CreateDealMsg),
     ExecuteDeal(Id),
     CancelDeal(Id),
-    UpdateConfig {
-        owner: Option<String>,
-        duration_range: Option<Vec<u64>>,
+    UpdateConfig(UpdateConfigMsg),
+    UpdateOwnership(OwnershipAction),
+    /// Stops new deals and bids from being created or executed, can be sent by the
+    /// owner or the pauser
+    Pause,
+    /// Resumes trading, can only be sent by the owner
+    Unpause,
+    /// Takes the pause right away from the pauser, can only be sent by the owner
+    RemovePauser,
+    /// Sets the trade limits of a denom, removes them if `limit` is not provided
+    SetTradeLimit {
+        denom: UncheckedDenom,
+        limit: Option<TradeLimit>,
+    },
+    /// Adds and removes entries of the denom allowlist or denylist, nft collections are
+    /// listed by address as cw20 entries
+    UpdateDenoms {
+        list: DenomList,
+        add: Vec<UncheckedDenom>,
+        remove: Vec<UncheckedDenom>,
+    },
+    Receive(Cw20ReceiveMsg),
+    ReceiveNft(Cw721ReceiveMsg),
+    CreateBid(CreateBidMsg),
+    ExecuteBid(Id),
+    CancelBid(Id),
+    WithdrawBid(Id),
+    ClaimBid(Id),
+    /// Refunds the sellers of up to `limit` open deals past their end time, deals holding
+    /// cw20 or nft tokens are left for their sellers to withdraw
+    ProcessExpired {
+        limit: Option<u32>,
     },
+    /// Upgrades up to `limit` deals a migration from 0.1 left in the old layout
+    MigrateDeals {
+        limit: Option<u32>,
+    },
+}
+
+/// Messages that can be embedded in a cw20 `Send`
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Creates a deal offering the received cw20 tokens
+    CreateDeal(CreateDealMsg),
+    /// Executes a deal paying its cw20 ask with the received tokens
+    ExecuteDeal(Id),
+    /// Creates a bid escrowing the received cw20 tokens
+    CreateBid(CreateBidMsg),
+    /// Fills a bid asking for the received cw20 tokens
+    ExecuteBid(Id),
+}
+
+/// Messages that can be embedded in a cw721 `SendNft`
+#[cw_serde]
+pub enum ReceiveNftMsg {
+    /// Creates a deal offering the received nft
+    CreateDeal(CreateDealMsg),
+}
+
+/// Cw721ReceiveMsg should be de/serialized under `ReceiveNft()` variant in a ExecuteMsg,
+/// mirrors the cw721 spec
+#[cw_serde]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
+/// The subset of the cw721 execute messages used by the contract
+#[cw_serde]
+pub enum Cw721ExecuteMsg {
+    TransferNft { recipient: String, token_id: String },
 }
 
 #[cw_serde]
@@ -30,18 +111,63 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     #[returns(Deal)]
     DealById(u64),
-    #[returns(Vec<Deal>)]
+    #[returns(Page<Deal>)]
     DealsByExpiration {
         options: Option<QueryOptions>,
         show_expired: bool,
     },
-    #[returns(Vec<Deal>)]
+    #[returns(Page<Deal>)]
     DealsByFilters {
-        filters: QueryFilter,
+        filters: Box<QueryFilter>,
+        options: Option<QueryOptions>,
+    },
+    #[returns(Page<Deal>)]
+    DealsForCounterparty {
+        counterparty: String,
+        options: Option<QueryOptions>,
+    },
+    #[returns(Bid)]
+    BidById(u64),
+    #[returns(Page<Bid>)]
+    BidsByFilters {
+        filters: BidFilter,
         options: Option<QueryOptions>,
     },
     #[returns(Config)]
     Config,
+    #[returns(Ownership)]
+    Ownership,
+    #[returns(bool)]
+    Paused,
+    #[returns(Option<TradeLimit>)]
+    TradeLimit(String),
+    #[returns(Page<DenomTradeLimit>)]
+    TradeLimits { options: Option<QueryOptions> },
+    #[returns(Page<String>)]
+    Denoms {
+        list: DenomList,
+        options: Option<QueryOptions>,
+    },
+    #[returns(StatsResponse)]
+    Stats,
+    /// The latest trade of a pair
+    #[returns(Option<PriceResponse>)]
+    LastPrice(Pair),
+    /// Trades of a pair sorted by time, optionally within a time range
+    #[returns(Page<Trade>)]
+    Trades {
+        pair: Pair,
+        time: Option<TimeRange>,
+        options: Option<QueryOptions>,
+    },
+    /// Funds held by the contract for the deals of a seller
+    #[returns(AccountResponse)]
+    Account {
+        address: String,
+        /// Most deals past their end time counted in the expired totals, 10 by default
+        /// and 100 at most. The oldest ones are counted first
+        limit: Option<u32>,
+    },
 }
 
 /// QueryOptions are used to paginate contract queries
@@ -50,21 +176,183 @@ pub enum QueryMsg {
 pub struct QueryOptions {
     /// Whether to sort items in ascending or descending order
     pub descending: Option<bool>,
-    /// The id to start the query after
-    pub start_after: Option<u64>,
+    /// The `next_cursor` of the previous page, the query starts from the first item if not provided
+    pub cursor: Option<Binary>,
     // The number of items that will be returned
     pub limit: Option<u32>,
 }
 
+/// A page of the items of a list query
+#[cw_serde]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the last item to pass in the options of the next query, `None` once
+    /// there are no items left
+    pub next_cursor: Option<Binary>,
+    /// Number of items matching the query across all pages, only provided when it is
+    /// kept in storage
+    pub total: Option<u64>,
+}
+
+/// Totals are by native denom or cw20 address, nfts are left out
+#[cw_serde]
+pub struct AccountResponse {
+    /// Offer escrowed in open deals before their end time
+    pub escrowed: Vec<Coin>,
+    /// Proceeds of executed deals waiting to be claimed
+    pub claimable: Vec<Coin>,
+    /// Offer of open deals past their end time, refunded on withdraw
+    pub expired: Vec<Coin>,
+    /// Whether the expired totals cover all the deals past their end time, if not only
+    /// the first `limit` of them are moved out of `escrowed` and the open deal count
+    pub expired_complete: bool,
+    pub deals: DealCounts,
+}
+
+/// Number of deals by status
+#[cw_serde]
+#[derive(Default)]
+pub struct DealCounts {
+    pub open: u64,
+    pub claimable: u64,
+    pub cancelled: u64,
+    pub closed: u64,
+    pub expired: u64,
+}
+
 #[cw_serde]
+pub struct PriceResponse {
+    /// The ask paid per unit of offer
+    pub price: Decimal,
+    pub trade: Trade,
+}
+
+#[cw_serde]
+pub struct StatsResponse {
+    pub stats: Stats,
+    /// Offer amounts bought from deals, the denom is a native denom or cw20 address
+    pub offer_volume: Vec<Coin>,
+    /// Ask amounts paid to deals, the denom is a native denom or cw20 address
+    pub ask_volume: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct DenomTradeLimit {
+    pub denom: String,
+    pub limit: TradeLimit,
+}
+
+/// Filters of a deals query, every provided field must match. The most selective
+/// index available is used for the lookup and the remaining fields are checked on
+/// each deal, all deals are returned when no field is set. A query reads at most 500
+/// deals, so a page can hold fewer items than its limit, or none, and still have a
+/// `next_cursor` to continue from
+#[cw_serde]
+#[derive(Default)]
 pub struct QueryFilter {
     pub seller: Option<String>,
+    /// Only deals executed by this buyer, including any of the buyers of partial deals
+    pub buyer: Option<String>,
     pub status: Option<DealStatus>,
+    /// Only deals offering an nft of this collection
+    pub collection: Option<String>,
+    /// Only deals trading a single asset for another, sorted by ascending price
+    /// unless `descending` is set
+    pub pair: Option<Pair>,
+    /// Only deals offering this native denom or cw20 address, alone or in a basket
+    pub offer_denom: Option<String>,
+    /// Only deals asking for this native denom or cw20 address, alone or in a basket
+    pub ask_denom: Option<String>,
+    /// Bounds on an offered amount, of `offer_denom` if set. Never matches nft offers
+    pub offer_amount: Option<AmountRange>,
+    /// Bounds on an asked amount, of `ask_denom` if set
+    pub ask_amount: Option<AmountRange>,
+    pub creation_time: Option<TimeRange>,
+    /// Deals are sorted by end time when this is the only indexed filter
+    pub end_time: Option<TimeRange>,
+}
+
+/// Inclusive bounds on an amount, open on the sides not provided
+#[cw_serde]
+#[derive(Default)]
+pub struct AmountRange {
+    pub min: Option<Uint128>,
+    pub max: Option<Uint128>,
+}
+
+/// Inclusive bounds on a time, open on the sides not provided
+#[cw_serde]
+#[derive(Default)]
+pub struct TimeRange {
+    pub start: Option<Timestamp>,
+    pub end: Option<Timestamp>,
+}
+
+/// Native denoms or cw20 addresses of both sides of a deal
+#[cw_serde]
+pub struct Pair {
+    pub offer_denom: String,
+    pub ask_denom: String,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct UpdateConfigMsg {
+    pub duration_policy: Option<DurationPolicy>,
+    pub fee_collector: Option<String>,
+    pub offer_fee_bps: Option<u16>,
+    pub ask_fee_bps: Option<u16>,
+    pub pauser: Option<String>,
+}
+
+#[cw_serde]
+pub enum OwnershipAction {
+    /// Offers ownership to `new_owner`, who has until `expiry` to accept it. A new
+    /// transfer replaces the pending one
+    TransferOwnership {
+        new_owner: String,
+        expiry: Option<Expiration>,
+    },
+    /// Accepts a pending ownership transfer, must be sent by the pending owner
+    AcceptOwnership,
+    /// Gives up ownership for good, leaving the config frozen
+    RenounceOwnership,
+}
+
+#[cw_serde]
+pub struct BidFilter {
+    pub buyer: Option<String>,
+    pub status: Option<DealStatus>,
+}
+
+#[cw_serde]
+pub struct CreateBidMsg {
+    /// The asset the buyer wants, the escrowed payment is taken from the funds
+    /// sent with the message or from the received cw20 tokens
+    pub ask: UncheckedAsset,
+    pub duration: u64,
+    /// Restricts the bid to this seller, anyone can fill it if not provided
+    pub allowed_seller: Option<String>,
 }
 
 #[cw_serde]
 pub struct CreateDealMsg {
-    pub offer: Coin,
-    pub ask: Coin,
+    /// Native coins escrowed by the deal, must match the funds sent with the
+    /// message exactly. Must be empty when the offer is a received cw20 or nft
+    pub offer: Vec<Coin>,
+    /// Assets the buyer must pay, a cw20 ask can't be combined with other assets
+    pub ask: Vec<UncheckedAsset>,
+    /// Seconds the deal stays open, ignored when `end_time` is set
+    #[serde(default)]
     pub duration: u64,
+    /// Absolute time at which the deal expires, must still fit the duration policy
+    pub end_time: Option<Timestamp>,
+    /// Lets buyers fill a portion of the deal, requires a single asset offer and ask
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// Pays the ask to the seller as soon as the deal is executed instead of waiting for a claim
+    #[serde(default)]
+    pub auto_settle: bool,
+    /// Restricts the deal to these buyers, anyone can execute it if not provided
+    pub allowed_buyers: Option<Vec<String>>,
 }
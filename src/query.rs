@@ -1,21 +1,34 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
-use cosmwasm_std::{to_json_binary, Addr, Binary, Deps, Env, Order, StdError, StdResult};
-use cw_storage_plus::Bound;
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, Coin, Deps, Env, Order, StdResult, Storage, Uint128,
+};
+use cw_storage_plus::{Bound, Map, PrimaryKey};
+use std::collections::BTreeMap;
 
 use crate::{
-    msg::{QueryFilter, QueryMsg, QueryOptions},
-    state::{deals, Config, Deal, CONFIG},
+    msg::{
+        AccountResponse, AmountRange, BidFilter, DealCounts, DenomTradeLimit, Page, Pair,
+        PriceResponse, QueryFilter, QueryMsg, QueryOptions, StatsResponse, TimeRange,
+    },
+    state::{
+        bids, deals, trades, Asset, Bid, Config, Deal, DealStatus, DenomList, Id, Offer, Ownership,
+        Trade, TradeLimit, ASK_VOLUME, BUYER_DEALS, CONFIG, COUNTERPARTY_DEALS, DEAL_COUNTS,
+        ESCROWED, OFFER_VOLUME, OWNERSHIP, PAUSED, SELLER_DEAL_COUNTS, STATS, TRADE_LIMITS,
+        UNCLAIMED,
+    },
 };
 
 const DEFAULT_QUERY_LIMIT: u32 = 10;
 const MAX_QUERY_LIMIT: u32 = 100;
+/// Most deals a filtered query reads before returning a shorter page
+const MAX_SCAN_LIMIT: usize = 500;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::DealById(id) => to_json_binary(&query_deal_by_id(deps, id)?),
+        QueryMsg::DealById(id) => to_json_binary(&query_deal_by_id(deps, env, id)?),
         QueryMsg::DealsByExpiration {
             options,
             show_expired,
@@ -26,21 +39,51 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             options,
         )?),
         QueryMsg::DealsByFilters { options, filters } => {
-            to_json_binary(&query_deals_by_filters(deps, filters, options)?)
+            to_json_binary(&query_deals_by_filters(deps, env, *filters, options)?)
+        }
+        QueryMsg::DealsForCounterparty {
+            counterparty,
+            options,
+        } => to_json_binary(&query_deals_for_counterparty(
+            deps,
+            env,
+            counterparty,
+            options,
+        )?),
+        QueryMsg::BidById(id) => to_json_binary(&query_bid_by_id(deps, id)?),
+        QueryMsg::BidsByFilters { filters, options } => {
+            to_json_binary(&query_bids_by_filters(deps, filters, options)?)
+        }
+        QueryMsg::Config => to_json_binary(&query_config(deps)?),
+        QueryMsg::Ownership => to_json_binary(&query_ownership(deps)?),
+        QueryMsg::Paused => to_json_binary(&query_paused(deps)?),
+        QueryMsg::TradeLimit(denom) => to_json_binary(&query_trade_limit(deps, denom)?),
+        QueryMsg::TradeLimits { options } => to_json_binary(&query_trade_limits(deps, options)?),
+        QueryMsg::Denoms { list, options } => to_json_binary(&query_denoms(deps, list, options)?),
+        QueryMsg::Stats => to_json_binary(&query_stats(deps)?),
+        QueryMsg::LastPrice(pair) => to_json_binary(&query_last_price(deps, pair)?),
+        QueryMsg::Trades {
+            pair,
+            time,
+            options,
+        } => to_json_binary(&query_trades(deps, pair, time, options)?),
+        QueryMsg::Account { address, limit } => {
+            to_json_binary(&query_account(deps, env, address, limit)?)
         }
-        QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
     }
 }
 
-pub fn query_deal_by_id(deps: Deps, id: u64) -> StdResult<Deal> {
-    deals().load(deps.storage, id)
+pub fn query_deal_by_id(deps: Deps, env: Env, id: u64) -> StdResult<Deal> {
+    let deal = deals().load(deps.storage, id)?;
+    Ok(deal.with_effective_status(env.block.time))
 }
 
 pub fn query_deals_by_filters(
     deps: Deps,
+    env: Env,
     filter: QueryFilter,
     query_options: Option<QueryOptions>,
-) -> StdResult<Vec<Deal>> {
+) -> StdResult<Page<Deal>> {
     let options = query_options.unwrap_or_default();
 
     let mut order = Order::Ascending;
@@ -55,47 +98,180 @@ pub fn query_deals_by_filters(
         .unwrap_or(DEFAULT_QUERY_LIMIT)
         .min(MAX_QUERY_LIMIT);
 
-    let (min, max) = match order {
-        Order::Ascending => (options.start_after.map(Bound::exclusive), None),
-        Order::Descending => (None, options.start_after.map(Bound::exclusive)),
-    };
+    let (min, max) = cursor_bounds(options.cursor.clone(), order);
 
-    match (filter.seller, filter.status) {
-        (Some(seller), None) => {
-            let result = deals()
-                .idx
-                .seller
-                .prefix(Addr::unchecked(seller))
-                .range(deps.storage, min, max, order)
-                .take(limit as usize)
-                .map(|item| item.map(|(_, v)| v))
-                .collect::<StdResult<_>>()?;
-            Ok(result)
-        }
-        (None, Some(status)) => {
-            let result = deals()
-                .idx
-                .status
-                .prefix(status.as_string())
-                .range(deps.storage, min, max, order)
-                .take(limit as usize)
-                .map(|item| item.map(|(_, v)| v))
-                .collect::<StdResult<_>>()?;
-            Ok(result)
-        }
-        (Some(seller), Some(status)) => {
-            let result = deals()
+    let deals = deals();
+    // The cursor of a page is the index key of its last deal, which is the deal id
+    // unless the index is sorted by another field
+    let mut cursor_key: fn(&Deal) -> Vec<u8> = |deal| deal.id.joined_key();
+    // Deals past their end time are still stored as open until withdrawn, so the
+    // status index can't be used to look up expired deals
+    let indexed_status = filter.status.clone().filter(|s| *s != DealStatus::Expired);
+
+    let iter = if let Some(collection) = filter.collection.clone() {
+        deals
+            .idx
+            .collection
+            .prefix(collection)
+            .range(deps.storage, min, max, order)
+    } else if let Some(pair) = filter.pair.clone() {
+        let (min, max) = cursor_bounds(options.cursor, order);
+        cursor_key = |deal| (deal.price(), deal.id).joined_key();
+        deals
+            .idx
+            .pair
+            .sub_prefix((pair.offer_denom, pair.ask_denom))
+            .range(deps.storage, min, max, order)
+    } else if let Some(buyer) = filter.buyer.clone().map(Addr::unchecked) {
+        // A deal only keeps its last buyer, earlier buyers of partial deals are found
+        // through their fills
+        let deals = &deals;
+        Box::new(
+            BUYER_DEALS
+                .prefix(&buyer)
+                .keys(deps.storage, min, max, order)
+                .map(move |id| {
+                    let id = id?;
+                    Ok((id, deals.load(deps.storage, id)?))
+                }),
+        )
+    } else if let Some(seller) = filter.seller.clone().map(Addr::unchecked) {
+        match indexed_status {
+            Some(status) => deals
                 .idx
                 .seller_status
-                .prefix((Addr::unchecked(seller), status.as_string()))
-                .range(deps.storage, min, max, order)
-                .take(limit as usize)
-                .map(|item| item.map(|(_, v)| v))
-                .collect::<StdResult<_>>()?;
-            Ok(result)
+                .prefix((seller, status.as_string()))
+                .range(deps.storage, min, max, order),
+            None => deals
+                .idx
+                .seller
+                .prefix(seller)
+                .range(deps.storage, min, max, order),
         }
-        (None, None) => Err(StdError::generic_err("No filters provided")),
+    } else if let Some(status) = indexed_status {
+        deals
+            .idx
+            .status
+            .prefix(status.as_string())
+            .range(deps.storage, min, max, order)
+    } else if filter.end_time.is_some() || filter.status == Some(DealStatus::Expired) {
+        // The range bounds only apply when there's no page to resume, the cursor is
+        // already within them
+        let (min, max) = cursor_bounds(options.cursor, order);
+        let range = filter.end_time.clone().unwrap_or_default();
+        // Deals only expire once past their end time, so later deals are skipped
+        let expired_end = (filter.status == Some(DealStatus::Expired)).then_some(env.block.time);
+        let start = range
+            .start
+            .map(|start| Bound::inclusive((start.seconds(), Id::MIN)));
+        let end = range
+            .end
+            .into_iter()
+            .chain(expired_end)
+            .min()
+            .map(|end| Bound::inclusive((end.seconds(), Id::MAX)));
+        cursor_key = |deal| (deal.end_time.seconds(), deal.id).joined_key();
+        deals
+            .idx
+            .end_time
+            .range(deps.storage, min.or(start), max.or(end), order)
+    } else {
+        deals.range(deps.storage, min, max, order)
+    };
+
+    let now = env.block.time;
+    let deals = iter.map(|item| item.map(|(_, deal)| deal.with_effective_status(now)));
+    Ok(Page {
+        total: count_deals(deps.storage, &filter)?,
+        ..paginate_scan(deals, limit, cursor_key, |deal| {
+            matches_filter(deps.storage, deal, &filter)
+        })?
+    })
+}
+
+/// Number of deals matching a filter on the seller and a status, `None` for other
+/// filters and for the statuses open deals can leave by expiring
+fn count_deals(storage: &dyn Storage, filter: &QueryFilter) -> StdResult<Option<u64>> {
+    let counted = QueryFilter {
+        seller: None,
+        status: None,
+        ..filter.clone()
+    } == QueryFilter::default();
+    if !counted || matches!(filter.status, Some(DealStatus::Open | DealStatus::Expired)) {
+        return Ok(None);
+    }
+
+    let status = filter.status.as_ref().map(DealStatus::as_string);
+    let total = match (filter.seller.as_ref().map(Addr::unchecked), status) {
+        (Some(seller), Some(status)) => SELLER_DEAL_COUNTS
+            .may_load(storage, (&seller, &status))?
+            .unwrap_or_default(),
+        (Some(seller), None) => SELLER_DEAL_COUNTS
+            .prefix(&seller)
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, count)| count))
+            .sum::<StdResult<_>>()?,
+        (None, Some(status)) => DEAL_COUNTS.may_load(storage, &status)?.unwrap_or_default(),
+        (None, None) => count_all_deals(storage)?,
+    };
+    Ok(Some(total))
+}
+
+fn count_all_deals(storage: &dyn Storage) -> StdResult<u64> {
+    DEAL_COUNTS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, count)| count))
+        .sum()
+}
+
+/// Whether a deal, with its effective status, matches every field of the filter
+fn matches_filter(storage: &dyn Storage, deal: &Deal, filter: &QueryFilter) -> bool {
+    let offer = match &deal.offer {
+        Offer::Fungible(assets) => assets.as_slice(),
+        Offer::Nft(_) => &[],
+    };
+
+    filter.seller.as_ref().is_none_or(|s| deal.seller == *s)
+        && filter
+            .buyer
+            .as_ref()
+            .is_none_or(|b| BUYER_DEALS.has(storage, (&Addr::unchecked(b), deal.id)))
+        && filter.status.as_ref().is_none_or(|s| deal.status == *s)
+        && filter
+            .collection
+            .as_ref()
+            .is_none_or(|c| deal.offer.collection() == *c)
+        && filter
+            .pair
+            .as_ref()
+            .is_none_or(|p| deal.pair() == (p.offer_denom.clone(), p.ask_denom.clone()))
+        && matches_assets(offer, &filter.offer_denom, &filter.offer_amount)
+        && matches_assets(&deal.ask, &filter.ask_denom, &filter.ask_amount)
+        && filter
+            .creation_time
+            .as_ref()
+            .is_none_or(|r| in_range(deal.creation_time, r.start, r.end))
+        && filter
+            .end_time
+            .as_ref()
+            .is_none_or(|r| in_range(deal.end_time, r.start, r.end))
+}
+
+/// Whether one of the assets has the denom and an amount within the range, when provided
+fn matches_assets(assets: &[Asset], denom: &Option<String>, amount: &Option<AmountRange>) -> bool {
+    if denom.is_none() && amount.is_none() {
+        return true;
     }
+    assets.iter().any(|asset| {
+        denom.as_ref().is_none_or(|d| asset.denom.to_string() == *d)
+            && amount
+                .as_ref()
+                .is_none_or(|r| in_range(asset.amount, r.min, r.max))
+    })
+}
+
+fn in_range<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> bool {
+    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
 }
 
 pub fn query_deals_by_expiration(
@@ -103,7 +279,7 @@ pub fn query_deals_by_expiration(
     env: Env,
     show_expired: bool,
     options: Option<QueryOptions>,
-) -> StdResult<Vec<Deal>> {
+) -> StdResult<Page<Deal>> {
     let options = options.unwrap_or_default();
 
     let mut order = Order::Ascending;
@@ -118,30 +294,407 @@ pub fn query_deals_by_expiration(
         .unwrap_or(DEFAULT_QUERY_LIMIT)
         .min(MAX_QUERY_LIMIT);
 
-    let now = env.block.time.seconds();
-
-    let (min, max) = match show_expired {
-        true => match order {
-            Order::Ascending => (Some(Bound::exclusive((0, 0))), None),
-            Order::Descending => (None, Some(Bound::exclusive((u64::MAX, 0)))),
-        },
-        false => match order {
-            Order::Ascending => (Some(Bound::exclusive((now, 0))), None),
-            Order::Descending => (None, Some(Bound::exclusive((now, 0)))),
-        },
+    let now = env.block.time;
+
+    // Deals ending at or before now are expired, the cursor of a later page is already past them
+    let (min, max) = cursor_bounds(options.cursor, order);
+    let min = match show_expired {
+        true => min,
+        false => min.or(Some(Bound::exclusive((now.seconds(), Id::MAX)))),
     };
 
-    let result = deals()
+    let deals = deals()
         .idx
         .end_time
         .range(deps.storage, min, max, order)
-        .take(limit as usize)
-        .map(|item| item.map(|(_, v)| v))
-        .collect::<StdResult<_>>()?;
+        .map(|item| item.map(|(_, v)| v.with_effective_status(now)))
+        // Time may have passed since the cursor was returned
+        .filter(|item| match item {
+            Ok(deal) => show_expired || deal.end_time > now,
+            Err(_) => true,
+        });
+    let page = paginate(deals, limit, |deal| {
+        (deal.end_time.seconds(), deal.id).joined_key()
+    })?;
+    // The counters can't tell apart the open deals that are past their end time
+    Ok(Page {
+        total: show_expired
+            .then(|| count_all_deals(deps.storage))
+            .transpose()?,
+        ..page
+    })
+}
+
+pub fn query_deals_for_counterparty(
+    deps: Deps,
+    env: Env,
+    counterparty: String,
+    options: Option<QueryOptions>,
+) -> StdResult<Page<Deal>> {
+    let options = options.unwrap_or_default();
+
+    let mut order = Order::Ascending;
+    if let Some(descending) = options.descending {
+        if descending {
+            order = Order::Descending;
+        }
+    };
+
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .min(MAX_QUERY_LIMIT);
+
+    let (min, max) = cursor_bounds(options.cursor, order);
+
+    let counterparty = deps.api.addr_validate(&counterparty)?;
+
+    let deals = COUNTERPARTY_DEALS
+        .prefix(&counterparty)
+        .keys(deps.storage, min, max, order)
+        .map(|id| {
+            let deal = deals().load(deps.storage, id?)?;
+            Ok(deal.with_effective_status(env.block.time))
+        });
+    paginate(deals, limit, |deal| deal.id.joined_key())
+}
+
+pub fn query_bid_by_id(deps: Deps, id: u64) -> StdResult<Bid> {
+    bids().load(deps.storage, id)
+}
+
+pub fn query_bids_by_filters(
+    deps: Deps,
+    filter: BidFilter,
+    query_options: Option<QueryOptions>,
+) -> StdResult<Page<Bid>> {
+    let options = query_options.unwrap_or_default();
+
+    let mut order = Order::Ascending;
+    if let Some(descending) = options.descending {
+        if descending {
+            order = Order::Descending;
+        }
+    };
+
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .min(MAX_QUERY_LIMIT);
+
+    let (min, max) = cursor_bounds(options.cursor, order);
+
+    let bids = bids();
+    let iter = match (filter.buyer, filter.status) {
+        (Some(buyer), None) => {
+            bids.idx
+                .buyer
+                .prefix(Addr::unchecked(buyer))
+                .range(deps.storage, min, max, order)
+        }
+        (None, Some(status)) => {
+            bids.idx
+                .status
+                .prefix(status.as_string())
+                .range(deps.storage, min, max, order)
+        }
+        (Some(buyer), Some(status)) => bids
+            .idx
+            .buyer_status
+            .prefix((Addr::unchecked(buyer), status.as_string()))
+            .range(deps.storage, min, max, order),
+        (None, None) => bids.range(deps.storage, min, max, order),
+    };
 
-    Ok(result)
+    paginate(iter.map(|item| item.map(|(_, v)| v)), limit, |bid| {
+        bid.id.joined_key()
+    })
 }
 
 pub fn query_config(deps: Deps) -> StdResult<Config> {
-    Ok(CONFIG.load(deps.storage)?)
+    CONFIG.load(deps.storage)
+}
+
+pub fn query_ownership(deps: Deps) -> StdResult<Ownership> {
+    OWNERSHIP.load(deps.storage)
+}
+
+pub fn query_paused(deps: Deps) -> StdResult<bool> {
+    Ok(PAUSED.may_load(deps.storage)?.unwrap_or_default())
+}
+
+pub fn query_denoms(
+    deps: Deps,
+    list: DenomList,
+    options: Option<QueryOptions>,
+) -> StdResult<Page<String>> {
+    let options = options.unwrap_or_default();
+    let order = match options.descending {
+        Some(true) => Order::Descending,
+        _ => Order::Ascending,
+    };
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .min(MAX_QUERY_LIMIT);
+    let (min, max) = cursor_bounds(options.cursor, order);
+
+    let denoms = list.map().keys(deps.storage, min, max, order);
+    paginate(denoms, limit, |denom| denom.as_bytes().to_vec())
+}
+
+pub fn query_trade_limit(deps: Deps, denom: String) -> StdResult<Option<TradeLimit>> {
+    TRADE_LIMITS.may_load(deps.storage, &denom)
+}
+
+pub fn query_trade_limits(
+    deps: Deps,
+    options: Option<QueryOptions>,
+) -> StdResult<Page<DenomTradeLimit>> {
+    let options = options.unwrap_or_default();
+    let order = match options.descending {
+        Some(true) => Order::Descending,
+        _ => Order::Ascending,
+    };
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .min(MAX_QUERY_LIMIT);
+    let (min, max) = cursor_bounds(options.cursor, order);
+
+    let limits = TRADE_LIMITS
+        .range(deps.storage, min, max, order)
+        .map(|item| item.map(|(denom, limit)| DenomTradeLimit { denom, limit }));
+    paginate(limits, limit, |limit| limit.denom.as_bytes().to_vec())
+}
+
+pub fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
+    Ok(StatsResponse {
+        stats: STATS.may_load(deps.storage)?.unwrap_or_default(),
+        offer_volume: volume(deps.storage, &OFFER_VOLUME)?,
+        ask_volume: volume(deps.storage, &ASK_VOLUME)?,
+    })
+}
+
+fn volume(storage: &dyn Storage, volume: &Map<&str, Uint128>) -> StdResult<Vec<Coin>> {
+    volume
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect()
+}
+
+pub fn query_last_price(deps: Deps, pair: Pair) -> StdResult<Option<PriceResponse>> {
+    trades()
+        .idx
+        .pair
+        .sub_prefix((pair.offer_denom, pair.ask_denom))
+        .range(deps.storage, None, None, Order::Descending)
+        .next()
+        .transpose()?
+        .map(|(_, trade)| {
+            Ok(PriceResponse {
+                price: trade.price()?,
+                trade,
+            })
+        })
+        .transpose()
+}
+
+pub fn query_trades(
+    deps: Deps,
+    pair: Pair,
+    time: Option<TimeRange>,
+    options: Option<QueryOptions>,
+) -> StdResult<Page<Trade>> {
+    let options = options.unwrap_or_default();
+
+    let mut order = Order::Ascending;
+    if let Some(descending) = options.descending {
+        if descending {
+            order = Order::Descending;
+        }
+    };
+
+    let limit = options
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .min(MAX_QUERY_LIMIT);
+
+    // The time bounds only apply when there's no page to resume, the cursor is already
+    // within them
+    let (min, max) = cursor_bounds(options.cursor, order);
+    let time = time.unwrap_or_default();
+    let start = time
+        .start
+        .map(|start| Bound::inclusive((start.seconds(), Id::MIN)));
+    let end = time
+        .end
+        .map(|end| Bound::inclusive((end.seconds(), Id::MAX)));
+
+    let trades = trades()
+        .idx
+        .pair
+        .sub_prefix((pair.offer_denom, pair.ask_denom))
+        .range(deps.storage, min.or(start), max.or(end), order)
+        .map(|item| item.map(|(_, trade)| trade));
+    paginate(trades, limit, |trade| {
+        (trade.time.seconds(), trade.id).joined_key()
+    })
+}
+
+pub fn query_account(
+    deps: Deps,
+    env: Env,
+    address: String,
+    limit: Option<u32>,
+) -> StdResult<AccountResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT);
+
+    let mut counts = DealCounts::default();
+    for item in
+        SELLER_DEAL_COUNTS
+            .prefix(&address)
+            .range(deps.storage, None, None, Order::Ascending)
+    {
+        let (status, count) = item?;
+        match status.as_str() {
+            "open" => counts.open = count,
+            "claimable" => counts.claimable = count,
+            "cancelled" => counts.cancelled = count,
+            "closed" => counts.closed = count,
+            "expired" => counts.expired = count,
+            _ => {}
+        }
+    }
+
+    // Open deals stay escrowed until withdrawn, so the ones past their end time are
+    // moved out of the escrowed totals. Only those are walked, up to the limit
+    let mut escrowed = balances(deps.storage, &ESCROWED, &address)?;
+    let mut expired = BTreeMap::new();
+    let max = Bound::InclusiveRaw((env.block.time.seconds(), Id::MAX).joined_key());
+    let mut past_end = deals()
+        .idx
+        .seller_status_end_time
+        .sub_prefix((address.clone(), DealStatus::Open.as_string()))
+        .range(deps.storage, None, Some(max), Order::Ascending)
+        .take(limit as usize + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let expired_complete = past_end.len() <= limit as usize;
+    past_end.truncate(limit as usize);
+    for (_, deal) in past_end {
+        counts.open = counts.open.saturating_sub(1);
+        counts.expired += 1;
+        for asset in deal.escrowed() {
+            let denom = asset.denom.to_string();
+            if let Some(amount) = escrowed.get_mut(&denom) {
+                *amount = amount.saturating_sub(asset.amount);
+            }
+            *expired.entry(denom).or_default() += asset.amount;
+        }
+    }
+
+    Ok(AccountResponse {
+        escrowed: into_coins(escrowed),
+        claimable: into_coins(balances(deps.storage, &UNCLAIMED, &address)?),
+        expired: into_coins(expired),
+        expired_complete,
+        deals: counts,
+    })
+}
+
+fn balances(
+    storage: &dyn Storage,
+    balances: &Map<(&Addr, &str), Uint128>,
+    address: &Addr,
+) -> StdResult<BTreeMap<String, Uint128>> {
+    balances
+        .prefix(address)
+        .range(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+fn into_coins(balances: BTreeMap<String, Uint128>) -> Vec<Coin> {
+    balances
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin { denom, amount })
+        .collect()
+}
+
+/// Bounds of a range resuming after the cursor of the previous page
+fn cursor_bounds<'a, K: PrimaryKey<'a>>(
+    cursor: Option<Binary>,
+    order: Order,
+) -> (Option<Bound<'a, K>>, Option<Bound<'a, K>>) {
+    let bound = cursor.map(|cursor| Bound::ExclusiveRaw(cursor.into()));
+    match order {
+        Order::Ascending => (bound, None),
+        Order::Descending => (None, bound),
+    }
+}
+
+/// Takes up to `limit` items, along with the cursor of the last one when more are left.
+/// `cursor_key` returns the key of an item in the ranged map or index
+/// Like [`paginate`] for items checked after the lookup, at most `MAX_SCAN_LIMIT` items
+/// are read so a filter matching few of them can't exhaust the query gas. A page cut
+/// short by the cap resumes after the last item read, even if it didn't match
+fn paginate_scan<T>(
+    items: impl Iterator<Item = StdResult<T>>,
+    limit: u32,
+    cursor_key: impl Fn(&T) -> Vec<u8>,
+    matches: impl Fn(&T) -> bool,
+) -> StdResult<Page<T>> {
+    let limit = limit as usize;
+    let mut page = vec![];
+    let mut scanned = 0;
+    let mut last = None;
+    for item in items.take(MAX_SCAN_LIMIT) {
+        let item = item?;
+        scanned += 1;
+        if !matches(&item) {
+            last = Some(item);
+            continue;
+        }
+        if page.len() == limit {
+            return Ok(Page {
+                next_cursor: page.last().map(|item| Binary::from(cursor_key(item))),
+                items: page,
+                total: None,
+            });
+        }
+        page.push(item);
+        last = None;
+    }
+    let last = last.as_ref().or(page.last());
+    let next_cursor = match scanned == MAX_SCAN_LIMIT {
+        true => last.map(|item| Binary::from(cursor_key(item))),
+        false => None,
+    };
+    Ok(Page {
+        items: page,
+        next_cursor,
+        total: None,
+    })
+}
+
+fn paginate<T>(
+    items: impl Iterator<Item = StdResult<T>>,
+    limit: u32,
+    cursor_key: impl Fn(&T) -> Vec<u8>,
+) -> StdResult<Page<T>> {
+    let limit = limit as usize;
+    let mut items = items.take(limit + 1).collect::<StdResult<Vec<_>>>()?;
+    let next_cursor = match items.len() > limit {
+        true => {
+            items.truncate(limit);
+            items.last().map(|item| Binary::from(cursor_key(item)))
+        }
+        false => None,
+    };
+    Ok(Page {
+        items,
+        next_cursor,
+        total: None,
+    })
 }
@@ -1,17 +1,167 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Coin, StdResult, Storage, Timestamp};
+use cosmwasm_std::{
+    ensure, to_json_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, Deps, Empty, StdError,
+    StdResult, Storage, Timestamp, Uint128, WasmMsg,
+};
+use cw_denom::{CheckedDenom, DenomError, UncheckedDenom};
 use cw_storage_macro::index_list;
-use cw_storage_plus::{IndexedMap, Item, MultiIndex, UniqueIndex};
+use cw_storage_plus::{IndexedMap, Item, Map, MultiIndex, UniqueIndex};
+use cw_utils::Expiration;
+use std::fmt;
+
+use crate::error::ContractError;
+use crate::msg::Cw721ExecuteMsg;
 
 #[cw_serde]
 pub struct Config {
-    pub owner: Addr,
-    /// Range of time in seconds that a deal can be open
-    pub duration_range: Vec<u64>,
+    /// Durations a deal or bid can be open for
+    pub duration_policy: DurationPolicy,
+    /// Address receiving the protocol fees
+    pub fee_collector: Addr,
+    /// Fee in basis points taken from the offer when a deal is executed
+    pub offer_fee_bps: u16,
+    /// Fee in basis points taken from the ask when it is paid out
+    pub ask_fee_bps: u16,
+    /// Address allowed to pause the contract besides the owner
+    pub pauser: Option<Addr>,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        ensure!(
+            self.offer_fee_bps <= MAX_FEE_BPS && self.ask_fee_bps <= MAX_FEE_BPS,
+            ContractError::InvalidFee(MAX_FEE_BPS)
+        );
+        self.duration_policy.validate()
+    }
+}
+
+/// Durations in seconds that a deal or bid can be open for
+#[cw_serde]
+pub enum DurationPolicy {
+    /// Any duration between `min` and `max`, inclusive
+    Range { min: u64, max: u64 },
+    /// Only the listed durations
+    Discrete(Vec<u64>),
 }
 
+impl DurationPolicy {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        match self {
+            DurationPolicy::Range { min, max } => ensure!(
+                *min > 0 && min <= max,
+                ContractError::InvalidDurationPolicy(
+                    "min must be greater than zero and can't exceed max".to_string()
+                )
+            ),
+            DurationPolicy::Discrete(durations) => ensure!(
+                !durations.is_empty() && !durations.contains(&0),
+                ContractError::InvalidDurationPolicy(
+                    "durations must be non empty and greater than zero".to_string()
+                )
+            ),
+        }
+        let longest = match self {
+            DurationPolicy::Range { max, .. } => *max,
+            DurationPolicy::Discrete(durations) => durations.iter().copied().max().unwrap_or(0),
+        };
+        ensure!(
+            longest <= MAX_DURATION,
+            ContractError::InvalidDurationPolicy(format!(
+                "durations can't exceed {MAX_DURATION} seconds"
+            ))
+        );
+        Ok(())
+    }
+
+    pub fn check(&self, duration: u64) -> Result<(), ContractError> {
+        match self {
+            DurationPolicy::Range { min, max } => ensure!(
+                (min..=max).contains(&&duration),
+                ContractError::InvalidDuration(*min, *max)
+            ),
+            DurationPolicy::Discrete(durations) => ensure!(
+                durations.contains(&duration),
+                ContractError::DurationNotAllowed(durations.clone())
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Hard cap on deal and bid durations, ten years
+pub const MAX_DURATION: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// Hard cap on the protocol fees, 10%
+pub const MAX_FEE_BPS: u16 = 1_000;
+
 pub const CONFIG: Item<Config> = Item::new("config");
 
+/// Admin of the contract, transferred in two steps so a typo can't lose control of it
+#[cw_serde]
+pub struct Ownership {
+    /// The current owner, `None` once ownership has been renounced
+    pub owner: Option<Addr>,
+    /// The address that has been offered ownership and has yet to accept it
+    pub pending_owner: Option<Addr>,
+    /// When the pending transfer can no longer be accepted
+    pub pending_expiry: Option<Expiration>,
+}
+
+impl Ownership {
+    pub fn assert_owner(&self, sender: &Addr) -> Result<(), ContractError> {
+        ensure!(
+            self.owner.as_ref() == Some(sender),
+            ContractError::Unauthorized
+        );
+        Ok(())
+    }
+}
+
+pub const OWNERSHIP: Item<Ownership> = Item::new("ownership");
+
+#[cw_serde]
+pub enum DenomList {
+    Allow,
+    Deny,
+}
+
+/// Native denoms or cw20 addresses that can be traded, every denom outside the
+/// denylist can be while it is empty
+pub const ALLOWED_DENOMS: Map<&str, Empty> = Map::new("allowed_denoms");
+
+/// Native denoms or cw20 addresses that can't be traded
+pub const DENIED_DENOMS: Map<&str, Empty> = Map::new("denied_denoms");
+
+impl DenomList {
+    pub fn as_string(&self) -> String {
+        match self {
+            DenomList::Allow => "allow".to_string(),
+            DenomList::Deny => "deny".to_string(),
+        }
+    }
+
+    pub fn map(&self) -> Map<'static, &'static str, Empty> {
+        match self {
+            DenomList::Allow => ALLOWED_DENOMS,
+            DenomList::Deny => DENIED_DENOMS,
+        }
+    }
+}
+
+/// Bounds on the amount of a denom that can be offered or asked in a single deal or bid
+#[cw_serde]
+pub struct TradeLimit {
+    pub min_amount: Option<Uint128>,
+    pub max_amount: Option<Uint128>,
+}
+
+/// Trade limits keyed by native denom or cw20 address
+pub const TRADE_LIMITS: Map<&str, TradeLimit> = Map::new("trade_limits");
+
+/// Whether new deals and bids are rejected, funds can still be taken out while paused
+pub const PAUSED: Item<bool> = Item::new("paused");
+
 pub type Id = u64;
 
 pub const ID_COUNT: Item<Id> = Item::new("id_count");
@@ -22,6 +172,14 @@ pub fn next_id(store: &mut dyn Storage) -> StdResult<Id> {
     Ok(id)
 }
 
+pub const BID_ID_COUNT: Item<Id> = Item::new("bid_id_count");
+
+pub fn next_bid_id(store: &mut dyn Storage) -> StdResult<Id> {
+    let id = BID_ID_COUNT.may_load(store)?.unwrap_or(1);
+    BID_ID_COUNT.save(store, &(id + 1))?;
+    Ok(id)
+}
+
 #[cw_serde]
 pub enum DealStatus {
     Open,
@@ -43,16 +201,260 @@ impl DealStatus {
     }
 }
 
+/// An amount of a native or cw20 token
+#[cw_serde]
+pub struct Asset {
+    pub denom: CheckedDenom,
+    pub amount: Uint128,
+}
+
+impl Asset {
+    /// Returns the message that transfers this asset from the contract to `recipient`
+    pub fn transfer_msg(&self, recipient: &Addr) -> StdResult<CosmosMsg> {
+        self.denom.get_transfer_to_message(recipient, self.amount)
+    }
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.denom)
+    }
+}
+
+impl From<Coin> for Asset {
+    fn from(coin: Coin) -> Self {
+        Asset {
+            denom: CheckedDenom::Native(coin.denom),
+            amount: coin.amount,
+        }
+    }
+}
+
+/// Converts coins into assets sorted by denom
+pub fn native_assets(coins: &[Coin]) -> Vec<Asset> {
+    let mut assets: Vec<Asset> = coins.iter().cloned().map(Asset::from).collect();
+    assets.sort_by_key(|a| a.denom.to_string());
+    assets
+}
+
+/// Returns the messages that transfer a basket of assets to `recipient`,
+/// native coins are grouped in a single bank message and zero amounts skipped
+pub fn transfer_msgs(assets: &[Asset], recipient: &Addr) -> StdResult<Vec<CosmosMsg>> {
+    let mut coins: Vec<Coin> = vec![];
+    let mut msgs = vec![];
+    for asset in assets.iter().filter(|a| !a.amount.is_zero()) {
+        match &asset.denom {
+            CheckedDenom::Native(denom) => match coins.iter_mut().find(|c| &c.denom == denom) {
+                Some(coin) => coin.amount += asset.amount,
+                None => coins.push(Coin::new(asset.amount.u128(), denom)),
+            },
+            CheckedDenom::Cw20(_) => msgs.push(asset.transfer_msg(recipient)?),
+        }
+    }
+    if !coins.is_empty() {
+        coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+        msgs.insert(
+            0,
+            BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins,
+            }
+            .into(),
+        );
+    }
+    Ok(msgs)
+}
+
+/// Formats a basket of assets as a comma separated list
+pub fn display_assets(assets: &[Asset]) -> String {
+    assets
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// An amount of a native or cw20 token that has not been validated yet
+#[cw_serde]
+pub struct UncheckedAsset {
+    pub denom: UncheckedDenom,
+    pub amount: Uint128,
+}
+
+impl UncheckedAsset {
+    /// Validates the denom, querying the token contract in case of a cw20
+    pub fn into_checked(self, deps: Deps) -> Result<Asset, DenomError> {
+        Ok(Asset {
+            denom: self.denom.into_checked(deps)?,
+            amount: self.amount,
+        })
+    }
+}
+
+impl From<Coin> for UncheckedAsset {
+    fn from(coin: Coin) -> Self {
+        UncheckedAsset {
+            denom: UncheckedDenom::Native(coin.denom),
+            amount: coin.amount,
+        }
+    }
+}
+
+/// A cw721 token escrowed by the contract
+#[cw_serde]
+pub struct Nft {
+    pub collection: Addr,
+    pub token_id: String,
+}
+
+/// What the seller escrows when creating a deal
+#[cw_serde]
+pub enum Offer {
+    Fungible(Vec<Asset>),
+    Nft(Nft),
+}
+
+impl Offer {
+    /// Returns the messages that transfer the offer from the contract to `recipient`
+    pub fn transfer_msgs(&self, recipient: &Addr) -> StdResult<Vec<CosmosMsg>> {
+        match self {
+            Offer::Fungible(assets) => transfer_msgs(assets, recipient),
+            Offer::Nft(nft) => Ok(vec![WasmMsg::Execute {
+                contract_addr: nft.collection.to_string(),
+                msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: recipient.to_string(),
+                    token_id: nft.token_id.clone(),
+                })?,
+                funds: vec![],
+            }
+            .into()]),
+        }
+    }
+
+    /// Collection of the offered nft, empty for fungible offers
+    pub fn collection(&self) -> String {
+        match self {
+            Offer::Fungible(_) => String::new(),
+            Offer::Nft(nft) => nft.collection.to_string(),
+        }
+    }
+}
+
+impl From<Coin> for Offer {
+    fn from(coin: Coin) -> Self {
+        Offer::Fungible(vec![coin.into()])
+    }
+}
+
 #[cw_serde]
 pub struct Deal {
     pub id: Id,
     pub seller: Addr,
     pub buyer: Option<Addr>,
-    pub offer: Coin,
-    pub ask: Coin,
+    pub offer: Offer,
+    pub ask: Vec<Asset>,
     pub status: DealStatus,
     pub creation_time: Timestamp,
     pub end_time: Timestamp,
+    /// Whether buyers can pay a portion of the ask for a pro-rata slice of the offer
+    pub allow_partial: bool,
+    /// Whether the ask is sent to the seller on execution, skipping the claimable state
+    pub auto_settle: bool,
+    /// Offer amount sent to buyers so far, only tracked on partial deals
+    pub filled_offer: Uint128,
+    /// Ask amount paid by buyers so far, only tracked on partial deals
+    pub filled_ask: Uint128,
+    /// Ask amount already sent to the seller, only tracked on partial deals
+    pub claimed_ask: Uint128,
+    /// Addresses allowed to execute the deal, anyone can if empty
+    pub allowed_buyers: Vec<Addr>,
+}
+
+impl Deal {
+    /// Returns the deal with the status it has at `now`, open deals past their end time
+    /// are expired even if the seller hasn't withdrawn them yet
+    pub fn with_effective_status(mut self, now: Timestamp) -> Self {
+        if self.status == DealStatus::Open && now >= self.end_time {
+            self.status = DealStatus::Expired;
+        }
+        self
+    }
+
+    /// Fungible offer the seller would get back if the deal was withdrawn, empty once
+    /// the deal is no longer open
+    pub fn escrowed(&self) -> Vec<Asset> {
+        match &self.offer {
+            Offer::Fungible(_) if self.status != DealStatus::Open => vec![],
+            Offer::Fungible(offer) if self.allow_partial => vec![Asset {
+                denom: offer[0].denom.clone(),
+                amount: offer[0].amount - self.filled_offer,
+            }],
+            Offer::Fungible(offer) => offer.clone(),
+            Offer::Nft(_) => vec![],
+        }
+    }
+
+    /// Ask paid by buyers that hasn't been sent to the seller yet
+    pub fn unclaimed(&self) -> Vec<Asset> {
+        if self.allow_partial {
+            let amount = self.filled_ask - self.claimed_ask;
+            return match amount.is_zero() {
+                true => vec![],
+                false => vec![Asset {
+                    denom: self.ask[0].denom.clone(),
+                    amount,
+                }],
+            };
+        }
+        match self.status {
+            DealStatus::Claimable => self.ask.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether the crank can refund the deal once expired, deals holding cw20 or nft tokens
+    /// are left to their seller so a failing token contract can't block the crank
+    pub fn refundable(&self) -> bool {
+        let native = |asset: &Asset| matches!(asset.denom, CheckedDenom::Native(_));
+        match &self.offer {
+            Offer::Fungible(offer) => {
+                self.status == DealStatus::Open
+                    && offer.iter().all(native)
+                    && self.ask.iter().all(native)
+            }
+            Offer::Nft(_) => false,
+        }
+    }
+
+    fn refundable_key(&self) -> String {
+        match self.refundable() {
+            true => DealStatus::Open.as_string(),
+            false => String::new(),
+        }
+    }
+
+    /// The offer and ask denoms of deals trading a single fungible asset for another,
+    /// empty for baskets and nfts
+    pub fn pair(&self) -> (String, String) {
+        match (&self.offer, self.ask.as_slice()) {
+            (Offer::Fungible(offer), [ask]) if offer.len() == 1 => {
+                (offer[0].denom.to_string(), ask.denom.to_string())
+            }
+            _ => (String::new(), String::new()),
+        }
+    }
+
+    /// The ask paid per unit of offer in decimal atomics, used to sort deals of a pair
+    pub fn price(&self) -> u128 {
+        match (&self.offer, self.ask.as_slice()) {
+            (Offer::Fungible(offer), [ask]) if offer.len() == 1 => {
+                Decimal::checked_from_ratio(ask.amount, offer[0].amount)
+                    .map(|price| price.atomics().u128())
+                    .unwrap_or(u128::MAX)
+            }
+            _ => 0,
+        }
+    }
 }
 
 #[index_list(Deal)]
@@ -61,7 +463,15 @@ pub struct DealIndexer<'a> {
     pub seller: MultiIndex<'a, Addr, Deal, Id>,
     pub status: MultiIndex<'a, String, Deal, Id>,
     pub end_time: MultiIndex<'a, u64, Deal, Id>,
+    /// Open deals the crank can refund sorted by end time, other deals are indexed under
+    /// an empty string
+    pub refundable: MultiIndex<'a, (String, u64), Deal, Id>,
     pub seller_status: MultiIndex<'a, (Addr, String), Deal, Id>,
+    /// Deals by seller and status sorted by end time
+    pub seller_status_end_time: MultiIndex<'a, (Addr, String, u64), Deal, Id>,
+    pub collection: MultiIndex<'a, String, Deal, Id>,
+    /// Deals by `(offer_denom, ask_denom)` sorted by price within each pair
+    pub pair: MultiIndex<'a, ((String, String), u128), Deal, Id>,
 }
 
 pub fn deals<'a>() -> IndexedMap<'a, u64, Deal, DealIndexer<'a>> {
@@ -82,11 +492,272 @@ pub fn deals<'a>() -> IndexedMap<'a, u64, Deal, DealIndexer<'a>> {
             "deals",
             "deals__end_time",
         ),
+        refundable: MultiIndex::new(
+            |_pk: &[u8], d: &Deal| (d.refundable_key(), d.end_time.seconds()),
+            "deals",
+            "deals__refundable",
+        ),
         seller_status: MultiIndex::new(
             |_pk: &[u8], d: &Deal| (d.seller.clone(), d.status.as_string()),
             "deals",
             "deals__seller__status",
         ),
+        seller_status_end_time: MultiIndex::new(
+            |_pk: &[u8], d: &Deal| (d.seller.clone(), d.status.as_string(), d.end_time.seconds()),
+            "deals",
+            "deals__seller__status__end_time",
+        ),
+        collection: MultiIndex::new(
+            |_pk: &[u8], d: &Deal| d.offer.collection(),
+            "deals",
+            "deals__collection",
+        ),
+        pair: MultiIndex::new(
+            |_pk: &[u8], d: &Deal| (d.pair(), d.price()),
+            "deals",
+            "deals__pair",
+        ),
     };
     IndexedMap::new("deals", indexes)
 }
+
+/// Number of deals by stored status, open deals past their end time are still counted as open
+pub const DEAL_COUNTS: Map<&str, u64> = Map::new("deal_counts");
+
+/// Number of deals by seller and stored status
+pub const SELLER_DEAL_COUNTS: Map<(&Addr, &str), u64> = Map::new("seller_deal_counts");
+
+/// Contract-wide deal numbers
+#[cw_serde]
+#[derive(Default)]
+pub struct Stats {
+    pub deals_created: u64,
+    /// Deals whose whole offer has been bought, once per deal for partial fills
+    pub deals_executed: u64,
+    pub deals_cancelled: u64,
+    /// Deals withdrawn or processed past their end time
+    pub deals_expired: u64,
+}
+
+pub const STATS: Item<Stats> = Item::new("stats");
+
+/// Cumulative offer amounts bought from deals, by native denom or cw20 address
+pub const OFFER_VOLUME: Map<&str, Uint128> = Map::new("offer_volume");
+
+/// Cumulative ask amounts paid to deals, by native denom or cw20 address
+pub const ASK_VOLUME: Map<&str, Uint128> = Map::new("ask_volume");
+
+/// Adds traded assets to the cumulative volume of one side of the deals
+pub fn add_volume(
+    store: &mut dyn Storage,
+    volume: &Map<&str, Uint128>,
+    assets: &[Asset],
+) -> StdResult<()> {
+    for asset in assets {
+        volume.update(store, &asset.denom.to_string(), |amount| -> StdResult<_> {
+            Ok(amount.unwrap_or_default().checked_add(asset.amount)?)
+        })?;
+    }
+    Ok(())
+}
+
+/// Moves a deal of `seller` between the status counters and updates the stats,
+/// `from` is `None` for new deals
+pub fn count_deal_status(
+    store: &mut dyn Storage,
+    seller: &Addr,
+    from: Option<&DealStatus>,
+    to: &DealStatus,
+) -> StdResult<()> {
+    if from == Some(to) {
+        return Ok(());
+    }
+
+    let mut stats = STATS.may_load(store)?.unwrap_or_default();
+    if from.is_none() {
+        stats.deals_created += 1;
+    }
+    match to {
+        DealStatus::Claimable => stats.deals_executed += 1,
+        // Claimed deals were already counted when executed
+        DealStatus::Closed if from != Some(&DealStatus::Claimable) => stats.deals_executed += 1,
+        DealStatus::Cancelled => stats.deals_cancelled += 1,
+        DealStatus::Expired => stats.deals_expired += 1,
+        _ => {}
+    }
+    STATS.save(store, &stats)?;
+
+    if let Some(from) = from {
+        let from = from.as_string();
+        DEAL_COUNTS.update(store, &from, |count| -> StdResult<_> {
+            Ok(count.unwrap_or_default().saturating_sub(1))
+        })?;
+        SELLER_DEAL_COUNTS.update(store, (seller, &from), |count| -> StdResult<_> {
+            Ok(count.unwrap_or_default().saturating_sub(1))
+        })?;
+    }
+    let to = to.as_string();
+    DEAL_COUNTS.update(store, &to, |count| -> StdResult<_> {
+        Ok(count.unwrap_or_default() + 1)
+    })?;
+    SELLER_DEAL_COUNTS.update(store, (seller, &to), |count| -> StdResult<_> {
+        Ok(count.unwrap_or_default() + 1)
+    })?;
+    Ok(())
+}
+
+/// Fungible offer of the open deals of each seller, by native denom or cw20 address
+pub const ESCROWED: Map<(&Addr, &str), Uint128> = Map::new("escrowed");
+
+/// Proceeds waiting to be claimed by each seller, by native denom or cw20 address
+pub const UNCLAIMED: Map<(&Addr, &str), Uint128> = Map::new("unclaimed");
+
+/// Moves the seller balances from what the deal held before a change, `None` for
+/// new deals, to what it holds now
+pub fn update_balances(
+    store: &mut dyn Storage,
+    previous: Option<&Deal>,
+    deal: &Deal,
+) -> StdResult<()> {
+    let escrowed = previous.map(Deal::escrowed).unwrap_or_default();
+    move_balances(store, &ESCROWED, &deal.seller, &escrowed, &deal.escrowed())?;
+    let unclaimed = previous.map(Deal::unclaimed).unwrap_or_default();
+    move_balances(
+        store,
+        &UNCLAIMED,
+        &deal.seller,
+        &unclaimed,
+        &deal.unclaimed(),
+    )
+}
+
+fn move_balances(
+    store: &mut dyn Storage,
+    balances: &Map<(&Addr, &str), Uint128>,
+    seller: &Addr,
+    before: &[Asset],
+    after: &[Asset],
+) -> StdResult<()> {
+    for asset in before {
+        let denom = asset.denom.to_string();
+        let balance = balances
+            .may_load(store, (seller, &denom))?
+            .unwrap_or_default()
+            .saturating_sub(asset.amount);
+        match balance.is_zero() {
+            true => balances.remove(store, (seller, &denom)),
+            false => balances.save(store, (seller, &denom), &balance)?,
+        }
+    }
+    for asset in after.iter().filter(|a| !a.amount.is_zero()) {
+        balances.update(
+            store,
+            (seller, &asset.denom.to_string()),
+            |balance| -> StdResult<_> {
+                Ok(balance.unwrap_or_default().checked_add(asset.amount)?)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Private deals by each of their allowed buyers
+pub const COUNTERPARTY_DEALS: Map<(&Addr, Id), Empty> = Map::new("counterparty_deals");
+
+/// Deals by each buyer that filled them, partial deals can have several
+pub const BUYER_DEALS: Map<(&Addr, Id), Empty> = Map::new("buyer_deals");
+
+pub const TRADE_ID_COUNT: Item<Id> = Item::new("trade_id_count");
+
+pub fn next_trade_id(store: &mut dyn Storage) -> StdResult<Id> {
+    let id = TRADE_ID_COUNT.may_load(store)?.unwrap_or(1);
+    TRADE_ID_COUNT.save(store, &(id + 1))?;
+    Ok(id)
+}
+
+/// A fill of a deal trading a single asset for another
+#[cw_serde]
+pub struct Trade {
+    pub id: Id,
+    pub deal_id: Id,
+    /// Offer amount sent to the buyer, before fees
+    pub offer: Asset,
+    /// Ask amount paid by the buyer, before fees
+    pub ask: Asset,
+    pub time: Timestamp,
+}
+
+impl Trade {
+    pub fn pair(&self) -> (String, String) {
+        (self.offer.denom.to_string(), self.ask.denom.to_string())
+    }
+
+    /// The ask paid per unit of offer
+    pub fn price(&self) -> StdResult<Decimal> {
+        Decimal::checked_from_ratio(self.ask.amount, self.offer.amount)
+            .map_err(|e| StdError::generic_err(e.to_string()))
+    }
+}
+
+#[index_list(Trade)]
+pub struct TradeIndexer<'a> {
+    /// Trades by `(offer_denom, ask_denom)` sorted by time within each pair
+    pub pair: MultiIndex<'a, ((String, String), u64), Trade, Id>,
+}
+
+pub fn trades<'a>() -> IndexedMap<'a, u64, Trade, TradeIndexer<'a>> {
+    let indexes = TradeIndexer {
+        pair: MultiIndex::new(
+            |_pk: &[u8], t: &Trade| (t.pair(), t.time.seconds()),
+            "trades",
+            "trades__pair",
+        ),
+    };
+    IndexedMap::new("trades", indexes)
+}
+
+/// A buyer-initiated request to buy `ask` in exchange for the escrowed `offer`
+#[cw_serde]
+pub struct Bid {
+    pub id: Id,
+    pub buyer: Addr,
+    /// The seller that filled the bid
+    pub seller: Option<Addr>,
+    pub offer: Asset,
+    pub ask: Asset,
+    pub status: DealStatus,
+    pub creation_time: Timestamp,
+    pub end_time: Timestamp,
+    /// Only this seller can fill the bid if set
+    pub allowed_seller: Option<Addr>,
+}
+
+#[index_list(Bid)]
+pub struct BidIndexer<'a> {
+    pub buyer: MultiIndex<'a, Addr, Bid, Id>,
+    pub status: MultiIndex<'a, String, Bid, Id>,
+    pub end_time: MultiIndex<'a, u64, Bid, Id>,
+    pub buyer_status: MultiIndex<'a, (Addr, String), Bid, Id>,
+}
+
+pub fn bids<'a>() -> IndexedMap<'a, u64, Bid, BidIndexer<'a>> {
+    let indexes = BidIndexer {
+        buyer: MultiIndex::new(|_pk: &[u8], b: &Bid| b.buyer.clone(), "bids", "bids__buyer"),
+        status: MultiIndex::new(
+            |_pk: &[u8], b: &Bid| b.status.as_string(),
+            "bids",
+            "bids__status",
+        ),
+        end_time: MultiIndex::new(
+            |_pk: &[u8], b: &Bid| b.end_time.seconds(),
+            "bids",
+            "bids__end_time",
+        ),
+        buyer_status: MultiIndex::new(
+            |_pk: &[u8], b: &Bid| (b.buyer.clone(), b.status.as_string()),
+            "bids",
+            "bids__buyer__status",
+        ),
+    };
+    IndexedMap::new("bids", indexes)
+}
@@ -1,7 +1,9 @@
 pub use anyhow::Result;
-pub use derivative::Derivative;
 
-pub use cosmwasm_std::{coin, Addr, BlockInfo, Coin, Empty, StdResult};
+pub use cosmwasm_std::{
+    coin, Addr, Binary, BlockInfo, Coin, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+    StdError, StdResult,
+};
 pub use cw_multi_test::{Contract, ContractWrapper};
 
 pub use crate::error::ContractError;
@@ -15,6 +17,7 @@ pub const SELLER: &str = "seller";
 pub const BUYER: &str = "buyer";
 pub const EXECUTOR: &str = "executor";
 pub const DEPLOYER: &str = "deployer";
+pub const FEE_COLLECTOR: &str = "fee_collector";
 pub const DENOM_1: &str = "ucosm";
 pub const DENOM_2: &str = "ustake";
 
@@ -22,3 +25,19 @@ pub fn contract_otc() -> Box<dyn Contract<Empty>> {
     let contract = ContractWrapper::new(execute, instantiate, query);
     Box::new(contract)
 }
+
+/// A cw20 contract whose transfers always fail, like a frozen or malicious token
+pub fn contract_frozen_token() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> {
+            Err(StdError::generic_err("transfers are frozen"))
+        },
+        |_: DepsMut, _: Env, _: MessageInfo, _: Empty| -> StdResult<Response> {
+            Ok(Response::new())
+        },
+        |_: Deps, _: Env, _: Empty| -> StdResult<Binary> {
+            Err(StdError::generic_err("no queries"))
+        },
+    );
+    Box::new(contract)
+}
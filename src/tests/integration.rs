@@ -1,3 +1,7 @@
+use cosmwasm_std::{to_json_binary, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_multi_test::Executor;
+
 use crate::tests::common::*;
 use crate::tests::suite::*;
 
@@ -5,16 +9,121 @@ use crate::tests::suite::*;
 fn should_create_a_deal() {
     let mut suite = OTCSuite::init().unwrap();
     let msg = CreateDealMsg {
-        ask: coin(100, DENOM_1),
-        offer: coin(1000, DENOM_2),
+        ask: vec![coin(100, DENOM_1).into()],
+        offer: vec![coin(1000, DENOM_2)],
         duration: 20000,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
     };
     suite
         .create_deal(&suite.seller.clone(), msg.clone())
         .unwrap();
 
     let deal = suite.query_deal_by_id(1).unwrap();
-    assert_eq!(deal.ask, msg.ask);
-    assert_eq!(deal.offer, msg.offer);
+    assert_eq!(deal.ask, vec![coin(100, DENOM_1).into()]);
+    assert_eq!(deal.offer, msg.offer[0].clone().into());
     assert_eq!(deal.seller, suite.seller);
 }
+
+#[test]
+fn should_trade_a_basket() {
+    let mut suite = OTCSuite::init().unwrap();
+    let seller = suite.seller.clone();
+    let buyer = suite.buyer.clone();
+    let msg = CreateDealMsg {
+        ask: vec![coin(100, DENOM_1).into()],
+        offer: vec![coin(1000, DENOM_1), coin(1000, DENOM_2)],
+        duration: 20000,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    suite.create_deal(&seller, msg).unwrap();
+
+    suite
+        .execute_deal(&buyer, 1, &[coin(100, DENOM_1)])
+        .unwrap();
+    suite.claim(&seller, 1).unwrap();
+
+    // seller started with 50000 DENOM_1 and 5000 DENOM_2
+    assert_eq!(
+        suite.query_balance(&seller, DENOM_1).unwrap(),
+        coin(49100, DENOM_1)
+    );
+    assert_eq!(
+        suite.query_balance(&seller, DENOM_2).unwrap(),
+        coin(4000, DENOM_2)
+    );
+    assert_eq!(
+        suite.query_balance(&buyer, DENOM_1).unwrap(),
+        coin(50900, DENOM_1)
+    );
+    assert_eq!(
+        suite.query_balance(&buyer, DENOM_2).unwrap(),
+        coin(6000, DENOM_2)
+    );
+}
+
+#[test]
+fn should_process_expired_deals_past_a_frozen_token() {
+    let mut suite = OTCSuite::init().unwrap();
+    let seller = suite.seller.clone();
+    let token_id = suite.app.store_code(contract_frozen_token());
+    let token = suite
+        .app
+        .instantiate_contract(token_id, seller.clone(), &Empty {}, &[], "frozen", None)
+        .unwrap();
+
+    // the token deal ends first, its refund would fail
+    let msg = CreateDealMsg {
+        ask: vec![coin(100, DENOM_1).into()],
+        offer: vec![],
+        duration: 20000,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    suite
+        .app
+        .execute_contract(
+            token.clone(),
+            suite.otc.clone(),
+            &ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: seller.to_string(),
+                amount: Uint128::new(1000),
+                msg: to_json_binary(&ReceiveMsg::CreateDeal(msg)).unwrap(),
+            }),
+            &[],
+        )
+        .unwrap();
+    let msg = CreateDealMsg {
+        ask: vec![coin(100, DENOM_1).into()],
+        offer: vec![coin(1000, DENOM_2)],
+        duration: 40000,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    suite.create_deal(&seller, msg).unwrap();
+
+    suite.fast_forward_block_time(40000);
+    suite.process_expired(&suite.buyer.clone(), None).unwrap();
+
+    // the native deal is refunded while the token deal is left to its seller
+    assert_eq!(
+        suite.query_balance(&seller, DENOM_2).unwrap(),
+        coin(5000, DENOM_2)
+    );
+    let res = suite.app.execute_contract(
+        seller.clone(),
+        suite.otc.clone(),
+        &ExecuteMsg::Withdraw(1),
+        &[],
+    );
+    assert!(res.is_err());
+}
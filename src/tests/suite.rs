@@ -5,8 +5,10 @@ use crate::tests::common::*;
 pub struct OTCSuite {
     pub app: App,
     // The account that deploys everything
+    #[allow(dead_code)]
     pub deployer: Addr,
     // The account that is owner
+    #[allow(dead_code)]
     pub executor: Addr,
     // seller address
     pub seller: Addr,
@@ -53,7 +55,11 @@ impl OTCSuite {
             executor.clone(),
             &InstantiateMsg {
                 owner: executor.to_string(),
-                duration_range: [20000, 40000, 60000].to_vec(),
+                duration_policy: DurationPolicy::Discrete(vec![20000, 40000, 60000]),
+                fee_collector: FEE_COLLECTOR.to_string(),
+                offer_fee_bps: 0,
+                ask_fee_bps: 0,
+                pauser: None,
             },
             &[],
             "otc_contract",
@@ -71,7 +77,7 @@ impl OTCSuite {
     }
 
     pub fn query_balance(&self, addr: &Addr, denom: &str) -> StdResult<Coin> {
-        Ok(self.app.wrap().query_balance(addr.as_str(), denom)?)
+        self.app.wrap().query_balance(addr.as_str(), denom)
     }
 
     pub fn query_deal_by_id(&self, id: Id) -> StdResult<Deal> {
@@ -89,7 +95,49 @@ impl OTCSuite {
                 sender.clone(),
                 self.otc.clone(),
                 &ExecuteMsg::CreateDeal(msg.clone()),
-                &[msg.offer],
+                &msg.offer,
+            )
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    pub fn execute_deal(
+        &mut self,
+        sender: &Addr,
+        id: Id,
+        funds: &[Coin],
+    ) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(
+                sender.clone(),
+                self.otc.clone(),
+                &ExecuteMsg::ExecuteDeal(id),
+                funds,
+            )
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    pub fn claim(&mut self, sender: &Addr, id: Id) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(
+                sender.clone(),
+                self.otc.clone(),
+                &ExecuteMsg::Claim(id),
+                &[],
+            )
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    pub fn process_expired(
+        &mut self,
+        sender: &Addr,
+        limit: Option<u32>,
+    ) -> Result<AppResponse, ContractError> {
+        self.app
+            .execute_contract(
+                sender.clone(),
+                self.otc.clone(),
+                &ExecuteMsg::ProcessExpired { limit },
+                &[],
             )
             .map_err(|err| err.downcast().unwrap())
     }
@@ -1,18 +1,41 @@
 use std::borrow::BorrowMut;
+use std::slice;
 
 use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier};
-use cosmwasm_std::{coin, Addr, Env, MemoryStorage, MessageInfo, OwnedDeps, StdError, Timestamp};
-use cw_denom::DenomError;
+use cosmwasm_std::{
+    attr, coin, to_json_binary, Addr, BankMsg, Coin, ContractResult, CosmosMsg, Decimal, Empty,
+    Env, MemoryStorage, MessageInfo, Order, OwnedDeps, StdResult, SystemResult, Timestamp, Uint128,
+    WasmMsg, WasmQuery,
+};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg, TokenInfoResponse};
+use cw_denom::{CheckedDenom, DenomError, UncheckedDenom};
 use cw_utils::{Expiration, PaymentError};
 
 use crate::error::ContractError;
-use crate::execute::{cancel_deal, claim, create_deal, execute_deal, update_config, withdraw};
-use crate::instantiate::instantiate;
-use crate::msg::{CreateDealMsg, InstantiateMsg, QueryFilter, QueryOptions};
+use crate::execute::{
+    cancel_bid, cancel_deal, claim, claim_bid, create_bid, create_deal, execute_bid, execute_deal,
+    pause, process_expired, receive, receive_nft, remove_pauser, set_trade_limit, unpause,
+    update_config, update_denoms, update_ownership, withdraw, withdraw_bid,
+};
+use crate::instantiate::{instantiate, CONTRACT_NAME, CONTRACT_VERSION};
+use crate::migrate::{migrate, migrate_deals, v0_1};
+use crate::msg::{
+    AccountResponse, AmountRange, BidFilter, CreateBidMsg, CreateDealMsg, Cw721ExecuteMsg,
+    Cw721ReceiveMsg, DealCounts, InstantiateMsg, MigrateMsg, OwnershipAction, Pair, QueryFilter,
+    QueryOptions, ReceiveMsg, ReceiveNftMsg, TimeRange, UpdateConfigMsg,
+};
 use crate::query::{
-    query_config, query_deal_by_id, query_deals_by_expiration, query_deals_by_filters,
+    query_account, query_bid_by_id, query_bids_by_filters, query_config, query_deal_by_id,
+    query_deals_by_expiration, query_deals_by_filters, query_deals_for_counterparty, query_denoms,
+    query_last_price, query_ownership, query_paused, query_stats, query_trade_limit,
+    query_trade_limits, query_trades,
+};
+use crate::state::{
+    deals, Asset, Config, Deal, DealStatus, DenomList, DurationPolicy, Nft, Offer, Ownership,
+    Stats, Trade, TradeLimit, UncheckedAsset, BUYER_DEALS, CONFIG, ID_COUNT, MAX_DURATION,
+    OWNERSHIP,
 };
-use crate::state::{deals, Config, Deal, DealStatus, CONFIG};
 
 const SELLER: &str = "seller";
 const BUYER: &str = "buyer";
@@ -28,7 +51,11 @@ fn do_instantiate() -> (
 
     let msg = InstantiateMsg {
         owner: "owner".to_string(),
-        duration_range: vec![500, 300],
+        duration_policy: DurationPolicy::Range { min: 300, max: 500 },
+        fee_collector: "fee_collector".to_string(),
+        offer_fee_bps: 0,
+        ask_fee_bps: 0,
+        pauser: None,
     };
 
     let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg.clone());
@@ -37,8 +64,9 @@ fn do_instantiate() -> (
     let cfg = CONFIG.load(&deps.storage).unwrap();
 
     // Verify config is right
-    assert_eq!(cfg.owner, msg.owner);
-    assert_eq!(cfg.duration_range, msg.duration_range);
+    let ownership = OWNERSHIP.load(&deps.storage).unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked(msg.owner)));
+    assert_eq!(cfg.duration_policy, msg.duration_policy);
 
     (deps, env, info)
 }
@@ -53,12 +81,18 @@ fn mock_data(env: &Env, deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier
         creation_time: env.block.time,
         end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
         buyer: Some(buyer.clone()),
-        ask: coin(12, "ucosm"),
-        offer: coin(100, "ustake"),
+        ask: vec![coin(12, "ucosm").into()],
+        offer: coin(100, "ustake").into(),
         status: DealStatus::Closed,
+        allow_partial: false,
+        auto_settle: false,
+        filled_offer: Uint128::zero(),
+        filled_ask: Uint128::zero(),
+        claimed_ask: Uint128::zero(),
+        allowed_buyers: vec![],
     };
 
-    let res = deals().save(deps.as_mut().storage, deal.id.clone(), &deal);
+    let res = deals().save(deps.as_mut().storage, deal.id, &deal);
     assert!(res.is_ok());
 
     let deal: Deal = Deal {
@@ -67,12 +101,18 @@ fn mock_data(env: &Env, deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier
         creation_time: env.block.time,
         end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
         buyer: Some(Addr::unchecked("another_buyer")),
-        ask: coin(12, "ucosm"),
-        offer: coin(100, "ustake"),
+        ask: vec![coin(12, "ucosm").into()],
+        offer: coin(100, "ustake").into(),
         status: DealStatus::Open,
+        allow_partial: false,
+        auto_settle: false,
+        filled_offer: Uint128::zero(),
+        filled_ask: Uint128::zero(),
+        claimed_ask: Uint128::zero(),
+        allowed_buyers: vec![],
     };
 
-    let res = deals().save(deps.as_mut().storage, deal.id.clone(), &deal);
+    let res = deals().save(deps.as_mut().storage, deal.id, &deal);
     assert!(res.is_ok());
 
     let deal: Deal = Deal {
@@ -81,12 +121,18 @@ fn mock_data(env: &Env, deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier
         creation_time: env.block.time,
         end_time: Timestamp::from_seconds(env.block.time.seconds()).minus_days(1),
         buyer: Some(Addr::unchecked("another_buyer")),
-        ask: coin(12, "ucosm"),
-        offer: coin(100, "ustake"),
+        ask: vec![coin(12, "ucosm").into()],
+        offer: coin(100, "ustake").into(),
         status: DealStatus::Open,
+        allow_partial: false,
+        auto_settle: false,
+        filled_offer: Uint128::zero(),
+        filled_ask: Uint128::zero(),
+        claimed_ask: Uint128::zero(),
+        allowed_buyers: vec![],
     };
 
-    let res = deals().save(deps.as_mut().storage, deal.id.clone(), &deal);
+    let res = deals().save(deps.as_mut().storage, deal.id, &deal);
     assert!(res.is_ok());
 
     let deal: Deal = Deal {
@@ -95,13 +141,46 @@ fn mock_data(env: &Env, deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier
         creation_time: env.block.time,
         end_time: Timestamp::from_seconds(env.block.time.seconds()).minus_days(1),
         buyer: Some(Addr::unchecked("another_buyer")),
-        ask: coin(12, "ucosm"),
-        offer: coin(100, "ustake"),
+        ask: vec![coin(12, "ucosm").into()],
+        offer: coin(100, "ustake").into(),
         status: DealStatus::Open,
+        allow_partial: false,
+        auto_settle: false,
+        filled_offer: Uint128::zero(),
+        filled_ask: Uint128::zero(),
+        claimed_ask: Uint128::zero(),
+        allowed_buyers: vec![],
     };
 
-    let res = deals().save(deps.as_mut().storage, deal.id.clone(), &deal);
+    let res = deals().save(deps.as_mut().storage, deal.id, &deal);
     assert!(res.is_ok());
+
+    // fills are recorded when deals are executed, which the mock deals skip
+    let another_buyer = Addr::unchecked("another_buyer");
+    for (buyer, id) in [
+        (&buyer, 1),
+        (&another_buyer, 2),
+        (&another_buyer, 3),
+        (&another_buyer, 4),
+    ] {
+        let res = BUYER_DEALS.save(deps.as_mut().storage, (buyer, id), &Empty {});
+        assert!(res.is_ok());
+    }
+}
+
+fn mock_config(deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>) {
+    CONFIG
+        .save(
+            deps.as_mut().storage,
+            &Config {
+                duration_policy: DurationPolicy::Range { min: 300, max: 500 },
+                fee_collector: Addr::unchecked("fee_collector"),
+                offer_fee_bps: 0,
+                ask_fee_bps: 0,
+                pauser: None,
+            },
+        )
+        .unwrap();
 }
 
 #[test]
@@ -110,8 +189,12 @@ fn test_query_deal_by_id() {
     let env = mock_env();
     mock_data(&env, deps.borrow_mut());
 
-    let res = query_deal_by_id(deps.as_ref(), 1);
+    let res = query_deal_by_id(deps.as_ref(), env.clone(), 1);
     assert_eq!(res.unwrap().id, 1);
+
+    // open deals past their end time are reported as expired
+    let res = query_deal_by_id(deps.as_ref(), env.clone(), 4).unwrap();
+    assert_eq!(res.status, DealStatus::Expired);
 }
 
 #[test]
@@ -120,72 +203,162 @@ fn test_query_deals_by_filters() {
     let env = mock_env();
     mock_data(&env, deps.borrow_mut());
 
-    // If not filter is provided should throw an error.
-    let filters = QueryFilter {
-        seller: None,
-        status: None,
-    };
-    let res = query_deals_by_filters(deps.as_ref(), filters, None);
-    assert_eq!(
-        res.unwrap_err(),
-        StdError::generic_err("No filters provided")
-    );
+    // no filters should return every deal
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), QueryFilter::default(), None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 4);
 
     // filter by seller should return 3 deals
     let filters = QueryFilter {
         seller: Some(SELLER.to_string()),
-        status: None,
+        ..Default::default()
     };
-    let res = query_deals_by_filters(deps.as_ref(), filters, None);
-    assert_eq!(res.unwrap().len(), 3);
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 3);
 
-    // filter by seller and status open should return 2 deals
+    // filter by seller and status open should return 1 deal, the other one is past its end time
     let filters = QueryFilter {
         seller: Some(SELLER.to_string()),
         status: Some(DealStatus::Open),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
+
+    // filter by seller and status expired should return the deal past its end time
+    let filters = QueryFilter {
+        seller: Some(SELLER.to_string()),
+        status: Some(DealStatus::Expired),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].id, 4);
+    assert_eq!(res[0].status, DealStatus::Expired);
+
+    // filter by status expired should return both deals past their end time
+    let filters = QueryFilter {
+        status: Some(DealStatus::Expired),
+        ..Default::default()
     };
-    let res = query_deals_by_filters(deps.as_ref(), filters, None);
-    assert_eq!(res.unwrap().len(), 2);
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.iter().map(|d| d.id).collect::<Vec<_>>(), vec![3, 4]);
 
     // filter by seller and status closed should return 1 deal
     let filters = QueryFilter {
         seller: Some(SELLER.to_string()),
         status: Some(DealStatus::Closed),
+        ..Default::default()
     };
-    let res = query_deals_by_filters(deps.as_ref(), filters, None);
-    assert_eq!(res.unwrap().len(), 1);
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
 
     // filter by seller and status open but providing query option with limit 1 should return 1
     let filters = QueryFilter {
         seller: Some(SELLER.to_string()),
         status: Some(DealStatus::Open),
+        ..Default::default()
     };
     let query_options = QueryOptions {
-        start_after: None,
+        cursor: None,
         limit: Some(1),
         descending: None,
     };
 
-    let res = query_deals_by_filters(deps.as_ref(), filters, Some(query_options));
-    assert_eq!(res.unwrap().len(), 1);
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, Some(query_options))
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
 
     // providing order descending should return a different order than no providing anyhting
     let filters = QueryFilter {
         seller: Some(SELLER.to_string()),
-        status: None,
+        ..Default::default()
     };
 
     let query_options = QueryOptions {
-        start_after: None,
+        cursor: None,
         limit: None,
         descending: Some(true),
     };
 
-    let res = query_deals_by_filters(deps.as_ref(), filters.clone(), Some(query_options));
-    assert_eq!(res.unwrap()[0].id, 4);
+    let res = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap()
+    .items;
+    assert_eq!(res[0].id, 4);
+
+    let res_two = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res_two[0].id, 1);
+
+    // filter by buyer should return the deals they bought
+    let filters = QueryFilter {
+        buyer: Some(BUYER.to_string()),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.iter().map(|d| d.id).collect::<Vec<_>>(), vec![1]);
+
+    // filter by buyer and status open should skip the deals past their end time
+    let filters = QueryFilter {
+        buyer: Some("another_buyer".to_string()),
+        status: Some(DealStatus::Open),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.iter().map(|d| d.id).collect::<Vec<_>>(), vec![2]);
+
+    // filter by buyer and seller should paginate like the seller path
+    let filters = QueryFilter {
+        seller: Some(SELLER.to_string()),
+        buyer: Some("another_buyer".to_string()),
+        ..Default::default()
+    };
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(1),
+        descending: None,
+    };
+    let page = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap();
+    assert_eq!(page.items.iter().map(|d| d.id).collect::<Vec<_>>(), vec![2]);
 
-    let res_two = query_deals_by_filters(deps.as_ref(), filters, None);
-    assert_eq!(res_two.unwrap()[0].id, 1);
+    // the next page resumes after the cursor and is the last one
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(1),
+        descending: None,
+    };
+    let page =
+        query_deals_by_filters(deps.as_ref(), env.clone(), filters, Some(query_options)).unwrap();
+    assert_eq!(page.items.iter().map(|d| d.id).collect::<Vec<_>>(), vec![4]);
+    assert_eq!(page.next_cursor, None);
 }
 
 #[test]
@@ -195,22 +368,69 @@ pub fn test_query_deals_by_expiration() {
     mock_data(&env, deps.borrow_mut());
 
     // It should only show 2 since the other 2 are expired
-    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), false, None);
-    assert_eq!(res.unwrap().len(), 2);
+    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), false, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 2);
 
     // It should show 4 since we are showing expired
-    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), true, None);
-    assert_eq!(res.unwrap().len(), 4);
+    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), true, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 4);
 
     // It should show 2 since we are showing expired and providing a limit of 2
     let query_options = QueryOptions {
-        start_after: None,
+        cursor: None,
         limit: Some(2),
         descending: None,
     };
 
-    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), true, Some(query_options));
-    assert_eq!(res.unwrap().len(), 2);
+    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), true, Some(query_options))
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 2);
+
+    // The cursor resumes the end time index after the last deal of the page
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(3),
+        descending: None,
+    };
+    let page =
+        query_deals_by_expiration(deps.as_ref(), env.clone(), true, Some(query_options)).unwrap();
+    assert_eq!(
+        page.items.iter().map(|d| d.id).collect::<Vec<_>>(),
+        vec![3, 4, 1]
+    );
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(3),
+        descending: None,
+    };
+    let page =
+        query_deals_by_expiration(deps.as_ref(), env.clone(), true, Some(query_options)).unwrap();
+    assert_eq!(page.items.iter().map(|d| d.id).collect::<Vec<_>>(), vec![2]);
+    assert_eq!(page.next_cursor, None);
+
+    // Descending pages still leave out the expired deals
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(1),
+        descending: Some(true),
+    };
+    let page =
+        query_deals_by_expiration(deps.as_ref(), env.clone(), false, Some(query_options)).unwrap();
+    assert_eq!(page.items.iter().map(|d| d.id).collect::<Vec<_>>(), vec![2]);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(1),
+        descending: Some(true),
+    };
+    let page =
+        query_deals_by_expiration(deps.as_ref(), env.clone(), false, Some(query_options)).unwrap();
+    assert_eq!(page.items.iter().map(|d| d.id).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(page.next_cursor, None);
 }
 
 #[test]
@@ -221,42 +441,112 @@ pub fn test_query_config() {
     assert!(res.is_ok());
 
     let cfg = res.unwrap();
-    assert_eq!(cfg.owner, "owner".to_string());
-    assert_eq!(cfg.duration_range, vec![500, 300]);
+    assert_eq!(cfg.fee_collector, "fee_collector".to_string());
+    assert_eq!(
+        cfg.duration_policy,
+        DurationPolicy::Range { min: 300, max: 500 }
+    );
 }
 
 #[test]
 pub fn test_update_config() {
-    let (mut deps, _env, info) = do_instantiate();
+    let (mut deps, env, info) = do_instantiate();
 
-    let res = update_config(deps.as_mut(), info.clone(), None, Some(vec![100, 200]));
+    let msg = UpdateConfigMsg {
+        duration_policy: Some(DurationPolicy::Range { min: 100, max: 200 }),
+        ..Default::default()
+    };
+    let res = update_config(deps.as_mut(), info.clone(), msg);
     assert!(res.is_ok());
 
     // Config should have changed
     let cfg = CONFIG.load(&deps.storage).unwrap();
-    assert_eq!(cfg.duration_range, vec![100, 200]);
-    assert_eq!(cfg.owner, "owner".to_string());
+    assert_eq!(
+        cfg.duration_policy,
+        DurationPolicy::Range { min: 100, max: 200 }
+    );
 
-    let res = update_config(
+    let action = OwnershipAction::TransferOwnership {
+        new_owner: "new_owner".to_string(),
+        expiry: None,
+    };
+    update_ownership(deps.as_mut(), env.clone(), info.clone(), action).unwrap();
+    update_ownership(
         deps.as_mut(),
-        info.clone(),
-        Some("new_owner".to_string()),
-        None,
+        env.clone(),
+        mock_info("new_owner", &[]),
+        OwnershipAction::AcceptOwnership,
+    )
+    .unwrap();
+
+    // should fail since the sender is not the owner anymore
+    let msg = UpdateConfigMsg {
+        offer_fee_bps: Some(10),
+        ..Default::default()
+    };
+    let res = update_config(deps.as_mut(), info.clone(), msg);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let info = mock_info("new_owner", &[]);
+
+    // should fail if the duration policy is invalid
+    let msg = UpdateConfigMsg {
+        duration_policy: Some(DurationPolicy::Range { min: 200, max: 100 }),
+        ..Default::default()
+    };
+    let res = update_config(deps.as_mut(), info.clone(), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InvalidDurationPolicy(
+            "min must be greater than zero and can't exceed max".to_string()
+        )
     );
-    assert!(res.is_ok());
 
-    // owner should have changed
-    let cfg = CONFIG.load(&deps.storage).unwrap();
-    assert_eq!(cfg.owner, "new_owner".to_string());
+    let msg = UpdateConfigMsg {
+        duration_policy: Some(DurationPolicy::Discrete(vec![])),
+        ..Default::default()
+    };
+    let res = update_config(deps.as_mut(), info.clone(), msg);
+    assert!(res.is_err());
 
-    // should fail since the sender is not the owner
-    let res = update_config(
-        deps.as_mut(),
-        info.clone(),
-        Some("test_new_owner".to_string()),
-        None,
+    // durations are capped so end times can't overflow
+    let msg = UpdateConfigMsg {
+        duration_policy: Some(DurationPolicy::Range {
+            min: 100,
+            max: u64::MAX,
+        }),
+        ..Default::default()
+    };
+    let res = update_config(deps.as_mut(), info.clone(), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InvalidDurationPolicy(format!(
+            "durations can't exceed {MAX_DURATION} seconds"
+        ))
+    );
+
+    // discrete durations only accept the listed values
+    let msg = UpdateConfigMsg {
+        duration_policy: Some(DurationPolicy::Discrete(vec![100, 300])),
+        ..Default::default()
+    };
+    update_config(deps.as_mut(), info, msg).unwrap();
+
+    let offer = coin(100, "ustake");
+    let msg = CreateDealMsg {
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ucosm").into()],
+        duration: 200,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), mock_env(), mock_info(SELLER, &[offer]), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DurationNotAllowed(vec![100, 300])
     );
-    assert!(res.is_err())
 }
 
 #[test]
@@ -264,22 +554,18 @@ pub fn test_create_deal() {
     let mut deps = mock_dependencies();
     let env = mock_env();
     let offer = coin(100, "ustake");
-    let info: MessageInfo = mock_info(SELLER, &[offer.clone()]);
+    let info: MessageInfo = mock_info(SELLER, slice::from_ref(&offer));
 
-    CONFIG
-        .save(
-            deps.as_mut().storage,
-            &Config {
-                owner: Addr::unchecked("owner"),
-                duration_range: vec![500, 300],
-            },
-        )
-        .unwrap();
+    mock_config(&mut deps);
 
     let msg = CreateDealMsg {
-        offer: offer.clone(),
-        ask: coin(12, "ucosm"),
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ucosm").into()],
         duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
     };
 
     let res = create_deal(deps.as_mut(), env.clone(), info.clone(), msg.clone());
@@ -293,13 +579,17 @@ pub fn test_create_deal() {
     assert_eq!(deal.status, DealStatus::Open);
     assert_eq!(deal.buyer, None);
     assert_eq!(deal.seller, info.sender);
-    assert_eq!(deal.ask, msg.ask);
-    assert_eq!(deal.offer, msg.offer);
+    assert_eq!(deal.ask, vec![coin(12, "ucosm").into()]);
+    assert_eq!(deal.offer, msg.offer[0].clone().into());
 
     let msg: CreateDealMsg = CreateDealMsg {
-        offer: offer.clone(),
-        ask: coin(12, "ba"),
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ba").into()],
         duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
     };
 
     // Should fail if the denom is invalid or not native
@@ -312,328 +602,3018 @@ pub fn test_create_deal() {
 
     // Should fail if the duration is not in the range
     let msg: CreateDealMsg = CreateDealMsg {
-        offer: offer.clone(),
-        ask: coin(12, "ucosm"),
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ucosm").into()],
         duration: 100,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
     };
 
     let res = create_deal(deps.as_mut(), env.clone(), info.clone(), msg.clone());
     assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::InvalidDuration(500, 300));
+    assert_eq!(res.unwrap_err(), ContractError::InvalidDuration(300, 500));
+
+    // a huge duration is rejected rather than overflowing the end time
+    let msg = CreateDealMsg {
+        duration: u64::MAX,
+        ..msg
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), info.clone(), msg);
+    assert_eq!(res.unwrap_err(), ContractError::InvalidDuration(300, 500));
+
+    // An absolute end time can be used instead of a duration
+    let end_time = env.block.time.plus_seconds(400);
+    let msg: CreateDealMsg = CreateDealMsg {
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ucosm").into()],
+        duration: 0,
+        end_time: Some(end_time),
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    create_deal(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
+    assert_eq!(deal.end_time, end_time);
+
+    // but it must still fit the duration policy
+    let msg: CreateDealMsg = CreateDealMsg {
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ucosm").into()],
+        duration: 0,
+        end_time: Some(env.block.time.minus_seconds(1)),
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), info.clone(), msg);
+    assert_eq!(res.unwrap_err(), ContractError::InvalidDuration(300, 500));
 }
 
 #[test]
-pub fn test_cancel_deal() {
+pub fn test_receive_cw20_deal() {
     let mut deps = mock_dependencies();
     let env = mock_env();
-    let offer = coin(100, "ustake");
-    let info: MessageInfo = mock_info(SELLER, &[offer.clone()]);
+    let token_info: MessageInfo = mock_info("token", &[]);
 
-    deals()
-        .save(
-            deps.as_mut().storage,
-            1,
-            &Deal {
-                id: 1,
-                seller: info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
-                buyer: None,
-                ask: coin(12, "ucosm"),
-                offer: offer.clone(),
-                status: DealStatus::Open,
-            },
-        )
-        .unwrap();
+    mock_config(&mut deps);
 
-    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(deal.id, 1);
-    assert_eq!(deal.status, DealStatus::Open);
+    let msg = CreateDealMsg {
+        offer: vec![],
+        ask: vec![coin(12, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
 
-    let res = cancel_deal(deps.as_mut(), env.clone(), info.clone(), 1);
-    assert!(res.is_ok());
-    assert_eq!(res.unwrap().messages.len(), 1);
+    let cw20_msg = Cw20ReceiveMsg {
+        sender: SELLER.to_string(),
+        amount: Uint128::new(100),
+        msg: to_json_binary(&ReceiveMsg::CreateDeal(msg.clone())).unwrap(),
+    };
 
-    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(deal.status, DealStatus::Cancelled);
+    // It shouldn't be possible to send native funds along with the cw20 hook
+    let res = receive(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("token", &[coin(1, "ucosm")]),
+        cw20_msg.clone(),
+    );
+    assert!(res.is_err());
 
-    deals()
-        .save(
-            deps.as_mut().storage,
-            2,
-            &Deal {
-                id: 2,
-                seller: info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).minus_hours(1),
-                buyer: None,
-                ask: coin(12, "ucosm"),
-                offer,
-                status: DealStatus::Open,
-            },
-        )
-        .unwrap();
+    let res = receive(deps.as_mut(), env.clone(), token_info.clone(), cw20_msg);
+    assert!(res.is_ok());
 
-    // It shouldn't be possible to cancel a deal that has expired
-    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
-    assert_eq!(deal.id, 2);
-    assert_eq!(deal.status, DealStatus::Open);
+    // The offer should be the received cw20 and the seller the original sender
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.seller, Addr::unchecked(SELLER));
+    assert_eq!(
+        deal.offer,
+        Offer::Fungible(vec![Asset {
+            denom: CheckedDenom::Cw20(token_info.sender.clone()),
+            amount: Uint128::new(100),
+        }])
+    );
+    assert_eq!(deal.ask, vec![coin(12, "ucosm").into()]);
 
-    let res = cancel_deal(deps.as_mut(), env.clone(), info.clone(), 2);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::DealExpired);
+    // Cancelling should refund the seller with a cw20 transfer
+    let res = cancel_deal(deps.as_mut(), env, mock_info(SELLER, &[]), 1).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token_info.sender.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: SELLER.to_string(),
+                amount: Uint128::new(100),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
 }
 
 #[test]
-pub fn test_execute_deal() {
+pub fn test_cw20_ask() {
     let mut deps = mock_dependencies();
     let env = mock_env();
     let offer = coin(100, "ustake");
-    let ask = coin(12, "ucosm");
-    let seller_info: MessageInfo = mock_info(SELLER, &[offer.clone()]);
-    let buyer_info: MessageInfo = mock_info(BUYER, &[ask.clone()]);
+    let seller_info: MessageInfo = mock_info(SELLER, slice::from_ref(&offer));
+
+    // Only "token" answers the cw20 token info query
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart { contract_addr, .. } if contract_addr == "token" => {
+            SystemResult::Ok(ContractResult::Ok(
+                to_json_binary(&TokenInfoResponse {
+                    name: "Token".to_string(),
+                    symbol: "TKN".to_string(),
+                    decimals: 6,
+                    total_supply: Uint128::new(1000),
+                })
+                .unwrap(),
+            ))
+        }
+        _ => SystemResult::Ok(ContractResult::Err("not a cw20".to_string())),
+    });
+
+    mock_config(&mut deps);
+
+    // Should fail if the ask is not a valid cw20
+    let msg = CreateDealMsg {
+        offer: vec![offer.clone()],
+        ask: vec![UncheckedAsset {
+            denom: UncheckedDenom::Cw20("not_token".to_string()),
+            amount: Uint128::new(50),
+        }],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), seller_info.clone(), msg);
+    assert!(matches!(
+        res.unwrap_err(),
+        ContractError::Denom(DenomError::InvalidCw20 { .. })
+    ));
 
-    deals()
-        .save(
-            deps.as_mut().storage,
-            1,
-            &Deal {
-                id: 1,
-                seller: seller_info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
-                buyer: None,
-                ask: ask.clone(),
-                offer: offer.clone(),
-                status: DealStatus::Open,
-            },
-        )
-        .unwrap();
+    let msg = CreateDealMsg {
+        offer: vec![offer],
+        ask: vec![UncheckedAsset {
+            denom: UncheckedDenom::Cw20("token".to_string()),
+            amount: Uint128::new(50),
+        }],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), seller_info.clone(), msg);
+    assert!(res.is_ok());
 
     let deal = deals().load(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(deal.id, 1);
-    assert_eq!(deal.status, DealStatus::Open);
-    // it shouldn't be possible to execute a deal without sending the right ask
-    let res = execute_deal(deps.as_mut(), env.clone(), mock_info(BUYER, &[]), 1);
-    assert!(res.is_err());
     assert_eq!(
-        res.unwrap_err(),
-        ContractError::Payment(PaymentError::NoFunds {})
+        deal.ask[0].denom,
+        CheckedDenom::Cw20(Addr::unchecked("token"))
     );
 
+    // It shouldn't be possible to pay a cw20 ask with native funds
     let res = execute_deal(
         deps.as_mut(),
         env.clone(),
-        mock_info(BUYER, &[coin(1, "ucosm")]),
+        mock_info(BUYER, &[coin(50, "ucosm")]),
         1,
     );
-    assert!(res.is_err());
     assert_eq!(
         res.unwrap_err(),
-        ContractError::InsufficientAmount(deal.ask.amount.clone().to_string())
+        ContractError::Payment(PaymentError::MissingDenom("token".to_string()))
     );
 
-    // It should be possible to execute the deal
-    let res = execute_deal(deps.as_mut(), env.clone(), buyer_info.clone(), 1);
+    let cw20_msg = Cw20ReceiveMsg {
+        sender: BUYER.to_string(),
+        amount: Uint128::new(50),
+        msg: to_json_binary(&ReceiveMsg::ExecuteDeal(1)).unwrap(),
+    };
+
+    // It shouldn't be possible to pay with a different cw20
+    let res = receive(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("other_token", &[]),
+        cw20_msg.clone(),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Payment(PaymentError::MissingDenom("token".to_string()))
+    );
+
+    // It should be possible to execute the deal sending the cw20
+    let res = receive(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("token", &[]),
+        cw20_msg,
+    );
     assert!(res.is_ok());
-    assert_eq!(res.unwrap().messages.len(), 1);
 
     let deal = deals().load(deps.as_ref().storage, 1).unwrap();
     assert_eq!(deal.status, DealStatus::Claimable);
-    assert_eq!(deal.buyer, Some(buyer_info.sender.clone()));
+    assert_eq!(deal.buyer, Some(Addr::unchecked(BUYER)));
 
-    deals()
-        .save(
-            deps.as_mut().storage,
-            2,
-            &Deal {
-                id: 2,
-                seller: seller_info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
-                buyer: None,
-                ask: ask.clone(),
-                offer: offer.clone(),
-                status: DealStatus::Expired,
-            },
-        )
-        .unwrap();
+    // Claiming should pay the seller with a cw20 transfer
+    let res = claim(deps.as_mut(), mock_info(SELLER, &[]), 1).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token".to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: SELLER.to_string(),
+                amount: Uint128::new(50),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+}
 
-    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
-    assert_eq!(deal.id, 2);
+#[test]
+pub fn test_nft_deal() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let collection_info: MessageInfo = mock_info("collection", &[]);
+
+    mock_config(&mut deps);
+
+    for token_id in ["1", "2"] {
+        let cw721_msg = Cw721ReceiveMsg {
+            sender: SELLER.to_string(),
+            token_id: token_id.to_string(),
+            msg: to_json_binary(&ReceiveNftMsg::CreateDeal(CreateDealMsg {
+                offer: vec![],
+                ask: vec![coin(12, "ucosm").into()],
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
+            }))
+            .unwrap(),
+        };
+        let res = receive_nft(
+            deps.as_mut(),
+            env.clone(),
+            collection_info.clone(),
+            cw721_msg,
+        );
+        assert!(res.is_ok());
+    }
+
+    // The offer should be the received nft and the seller the original sender
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.seller, Addr::unchecked(SELLER));
+    assert_eq!(
+        deal.offer,
+        Offer::Nft(Nft {
+            collection: collection_info.sender.clone(),
+            token_id: "1".to_string(),
+        })
+    );
+
+    // Executing the deal should transfer the nft to the buyer
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "collection".to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: BUYER.to_string(),
+                token_id: "1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    // Cancelling should return the nft to the seller
+    let res = cancel_deal(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 2).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "collection".to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: SELLER.to_string(),
+                token_id: "2".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    // filter by collection should return both deals
+    let filters = QueryFilter {
+        collection: Some("collection".to_string()),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 2);
+
+    // filter by collection and status cancelled should return the second deal
+    let filters = QueryFilter {
+        seller: Some(SELLER.to_string()),
+        status: Some(DealStatus::Cancelled),
+        collection: Some("collection".to_string()),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].id, 2);
+}
+
+#[test]
+pub fn test_basket_deal() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let offer = vec![coin(1000, "uatom"), coin(500, "uosmo")];
+    let ask = vec![coin(20000, "uusdc").into(), coin(10, "ucosm").into()];
+    let seller_info: MessageInfo = mock_info(SELLER, &offer);
+
+    mock_config(&mut deps);
+
+    // Should fail if the offer doesn't match the funds sent
+    let msg = CreateDealMsg {
+        offer: vec![coin(1000, "uatom")],
+        ask: ask.clone(),
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), seller_info.clone(), msg);
+    assert_eq!(res.unwrap_err(), ContractError::InvalidOffer);
+
+    // Should fail if the ask repeats a denom
+    let msg = CreateDealMsg {
+        offer: offer.clone(),
+        ask: vec![coin(10, "ucosm").into(), coin(10, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), seller_info.clone(), msg);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InvalidAsk("denoms must be unique".to_string())
+    );
+
+    // The order of the basket shouldn't matter
+    let msg = CreateDealMsg {
+        offer: vec![coin(500, "uosmo"), coin(1000, "uatom")],
+        ask,
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(deps.as_mut(), env.clone(), seller_info.clone(), msg);
+    assert!(res.is_ok());
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(
+        deal.offer,
+        Offer::Fungible(vec![coin(1000, "uatom").into(), coin(500, "uosmo").into()])
+    );
+    assert_eq!(
+        deal.ask,
+        vec![coin(10, "ucosm").into(), coin(20000, "uusdc").into()]
+    );
+
+    // It shouldn't be possible to execute the deal without the full ask
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(20000, "uusdc")]),
+        1,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Payment(PaymentError::MissingDenom("ucosm".to_string()))
+    );
+
+    // It shouldn't be possible to send more denoms than the ask
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(
+            BUYER,
+            &[coin(20000, "uusdc"), coin(10, "ucosm"), coin(1, "uatom")],
+        ),
+        1,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Payment(PaymentError::ExtraDenom("uatom".to_string()))
+    );
+
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(20000, "uusdc"), coin(9, "ucosm")]),
+        1,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InsufficientAmount("10ucosm,20000uusdc".to_string())
+    );
+
+    // Executing the deal should send the whole offer to the buyer
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(20000, "uusdc"), coin(10, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: BUYER.to_string(),
+            amount: vec![coin(1000, "uatom"), coin(500, "uosmo")],
+        })
+    );
+
+    // Claiming should send the whole ask to the seller
+    let res = claim(deps.as_mut(), mock_info(SELLER, &[]), 1).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(10, "ucosm"), coin(20000, "uusdc")],
+        })
+    );
+}
+
+#[test]
+pub fn test_partial_fills() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let seller_info: MessageInfo = mock_info(SELLER, &[coin(100, "ustake")]);
+
+    mock_config(&mut deps);
+
+    // Partial fills are only supported on single asset deals
+    let msg = CreateDealMsg {
+        offer: vec![coin(100, "ustake"), coin(100, "uatom")],
+        ask: vec![coin(30, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: true,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake"), coin(100, "uatom")]),
+        msg,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::PartialFillNotSupported);
+
+    let msg = CreateDealMsg {
+        offer: vec![coin(100, "ustake")],
+        ask: vec![coin(30, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: true,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    create_deal(deps.as_mut(), env.clone(), seller_info.clone(), msg).unwrap();
+
+    // Paying a third of the ask should give a third of the offer rounded down
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(10, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: BUYER.to_string(),
+            amount: vec![coin(33, "ustake")],
+        })
+    );
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Open);
+    assert_eq!(deal.filled_offer, Uint128::new(33));
+    assert_eq!(deal.filled_ask, Uint128::new(10));
+
+    // It shouldn't be possible to pay more than what is left of the ask
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(25, "ucosm")]),
+        1,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::ExceedsRemaining("20ucosm".to_string())
+    );
+
+    // The seller can claim the proceeds while the deal is open
+    let res = claim(deps.as_mut(), seller_info.clone(), 1).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(10, "ucosm")],
+        })
+    );
+    let res = claim(deps.as_mut(), seller_info.clone(), 1);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(1, "ucosm")]),
+        1,
+    )
+    .unwrap();
+
+    // The last fill should take the whole remainder of the offer
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("another_buyer", &[coin(19, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "another_buyer".to_string(),
+            amount: vec![coin(64, "ustake")],
+        })
+    );
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Claimable);
+    assert_eq!(deal.filled_offer, Uint128::new(100));
+    assert_eq!(deal.filled_ask, Uint128::new(30));
+
+    let res = claim(deps.as_mut(), seller_info.clone(), 1).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(20, "ucosm")],
+        })
+    );
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Closed);
+
+    let msg = CreateDealMsg {
+        offer: vec![coin(10, "ustake")],
+        ask: vec![coin(100, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: true,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(10, "ustake")]),
+        msg,
+    )
+    .unwrap();
+
+    // A payment that buys less than one unit of the offer should be rejected
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(5, "ucosm")]),
+        2,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::FillTooSmall);
+
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(50, "ucosm")]),
+        2,
+    )
+    .unwrap();
+
+    // Withdrawing after expiry should return the unfilled offer and the unclaimed proceeds
+    env.block.time = env.block.time.plus_seconds(501);
+    let res = withdraw(deps.as_mut(), env, seller_info, 2).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(5, "ustake")],
+        })
+    );
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(50, "ucosm")],
+        })
+    );
+
+    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
     assert_eq!(deal.status, DealStatus::Expired);
+    assert_eq!(deal.claimed_ask, deal.filled_ask);
+}
+
+#[test]
+pub fn test_private_deal() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+    let offer = coin(100, "ustake");
+
+    mock_config(&mut deps);
+
+    let msg = CreateDealMsg {
+        offer: vec![offer.clone()],
+        ask: vec![coin(12, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: Some(vec![BUYER.to_string(), BUYER.to_string()]),
+    };
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, slice::from_ref(&offer)),
+        msg,
+    )
+    .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.allowed_buyers, vec![Addr::unchecked(BUYER)]);
+
+    // The designated buyer should find the deal
+    let res = query_deals_for_counterparty(deps.as_ref(), env.clone(), BUYER.to_string(), None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].id, 1);
+
+    let res = query_deals_for_counterparty(
+        deps.as_ref(),
+        env.clone(),
+        "another_buyer".to_string(),
+        None,
+    )
+    .unwrap()
+    .items;
+    assert!(res.is_empty());
+
+    // It shouldn't be possible to execute the deal without being designated
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("another_buyer", &[coin(12, "ucosm")]),
+        1,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::BuyerNotAllowed);
+
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    );
+    assert!(res.is_ok());
+}
+
+#[test]
+pub fn test_bids() {
+    let mut deps = mock_dependencies();
+    let mut env = mock_env();
+    let buyer_info: MessageInfo = mock_info(BUYER, &[coin(100, "ucosm")]);
+
+    mock_config(&mut deps);
+
+    let msg = CreateBidMsg {
+        ask: coin(50, "ustake").into(),
+        duration: 500,
+        allowed_seller: Some(SELLER.to_string()),
+    };
+    create_bid(deps.as_mut(), env.clone(), buyer_info.clone(), msg).unwrap();
+
+    let bid = query_bid_by_id(deps.as_ref(), 1).unwrap();
+    assert_eq!(bid.buyer, buyer_info.sender);
+    assert_eq!(bid.offer, coin(100, "ucosm").into());
+    assert_eq!(bid.ask, coin(50, "ustake").into());
+    assert_eq!(bid.status, DealStatus::Open);
+
+    // It shouldn't be possible to fill the bid without being the designated seller
+    let res = execute_bid(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("another_seller", &[coin(50, "ustake")]),
+        1,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::SellerNotAllowed);
+
+    let res = execute_bid(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(49, "ustake")]),
+        1,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InsufficientAmount("50ustake".to_string())
+    );
+
+    // Filling the bid should send the escrowed payment to the seller
+    let res = execute_bid(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(50, "ustake")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(100, "ucosm")],
+        })
+    );
+
+    // Only the buyer can claim the filled bid
+    let res = claim_bid(deps.as_mut(), mock_info(SELLER, &[]), 1);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let res = claim_bid(deps.as_mut(), buyer_info.clone(), 1).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: BUYER.to_string(),
+            amount: vec![coin(50, "ustake")],
+        })
+    );
+    assert_eq!(
+        query_bid_by_id(deps.as_ref(), 1).unwrap().status,
+        DealStatus::Closed
+    );
+
+    // A bid can also escrow cw20 tokens
+    let cw20_msg = Cw20ReceiveMsg {
+        sender: BUYER.to_string(),
+        amount: Uint128::new(10),
+        msg: to_json_binary(&ReceiveMsg::CreateBid(CreateBidMsg {
+            ask: coin(5, "ustake").into(),
+            duration: 300,
+            allowed_seller: None,
+        }))
+        .unwrap(),
+    };
+    receive(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("token", &[]),
+        cw20_msg,
+    )
+    .unwrap();
+
+    let res = cancel_bid(deps.as_mut(), env.clone(), buyer_info.clone(), 2).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token".to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: BUYER.to_string(),
+                amount: Uint128::new(10),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    );
+
+    let msg = CreateBidMsg {
+        ask: coin(50, "ustake").into(),
+        duration: 300,
+        allowed_seller: None,
+    };
+    create_bid(deps.as_mut(), env.clone(), buyer_info.clone(), msg).unwrap();
+
+    // It shouldn't be possible to withdraw before the bid expires
+    let res = withdraw_bid(deps.as_mut(), env.clone(), buyer_info.clone(), 3);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    env.block.time = env.block.time.plus_seconds(301);
+
+    let res = execute_bid(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(50, "ustake")]),
+        3,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::BidExpired);
+
+    let res = withdraw_bid(deps.as_mut(), env.clone(), buyer_info.clone(), 3);
+    assert_eq!(res.unwrap().messages.len(), 1);
+
+    // filter by buyer should return all the bids
+    let filters = BidFilter {
+        buyer: Some(BUYER.to_string()),
+        status: None,
+    };
+    let res = query_bids_by_filters(deps.as_ref(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 3);
+
+    let filters = BidFilter {
+        buyer: Some(BUYER.to_string()),
+        status: Some(DealStatus::Expired),
+    };
+    let res = query_bids_by_filters(deps.as_ref(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 1);
+    assert_eq!(res[0].id, 3);
+}
+
+#[test]
+pub fn test_cancel_deal() {
+    let mut deps = mock_dependencies();
+    mock_config(&mut deps);
+    let env = mock_env();
+    let offer = coin(100, "ustake");
+    let info: MessageInfo = mock_info(SELLER, slice::from_ref(&offer));
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            1,
+            &Deal {
+                id: 1,
+                seller: info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
+                buyer: None,
+                ask: vec![coin(12, "ucosm").into()],
+                offer: offer.clone().into(),
+                status: DealStatus::Open,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.id, 1);
+    assert_eq!(deal.status, DealStatus::Open);
+
+    let res = cancel_deal(deps.as_mut(), env.clone(), info.clone(), 1);
+    assert!(res.is_ok());
+    assert_eq!(res.unwrap().messages.len(), 1);
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Cancelled);
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            2,
+            &Deal {
+                id: 2,
+                seller: info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).minus_hours(1),
+                buyer: None,
+                ask: vec![coin(12, "ucosm").into()],
+                offer: offer.into(),
+                status: DealStatus::Open,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    // It shouldn't be possible to cancel a deal that has expired
+    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
+    assert_eq!(deal.id, 2);
+    assert_eq!(deal.status, DealStatus::Open);
+
+    let res = cancel_deal(deps.as_mut(), env.clone(), info.clone(), 2);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::DealExpired);
+}
+
+#[test]
+pub fn test_execute_deal() {
+    let mut deps = mock_dependencies();
+    mock_config(&mut deps);
+    let env = mock_env();
+    let offer = coin(100, "ustake");
+    let ask = coin(12, "ucosm");
+    let seller_info: MessageInfo = mock_info(SELLER, slice::from_ref(&offer));
+    let buyer_info: MessageInfo = mock_info(BUYER, slice::from_ref(&ask));
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            1,
+            &Deal {
+                id: 1,
+                seller: seller_info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
+                buyer: None,
+                ask: vec![ask.clone().into()],
+                offer: offer.clone().into(),
+                status: DealStatus::Open,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.id, 1);
+    assert_eq!(deal.status, DealStatus::Open);
+    // it shouldn't be possible to execute a deal without sending the right ask
+    let res = execute_deal(deps.as_mut(), env.clone(), mock_info(BUYER, &[]), 1);
+    assert!(res.is_err());
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Payment(PaymentError::NoFunds {})
+    );
+
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(1, "ucosm")]),
+        1,
+    );
+    assert!(res.is_err());
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InsufficientAmount("12ucosm".to_string())
+    );
+
+    // It should be possible to execute the deal
+    let res = execute_deal(deps.as_mut(), env.clone(), buyer_info.clone(), 1);
+    assert!(res.is_ok());
+    assert_eq!(res.unwrap().messages.len(), 1);
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Claimable);
+    assert_eq!(deal.buyer, Some(buyer_info.sender.clone()));
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            2,
+            &Deal {
+                id: 2,
+                seller: seller_info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
+                buyer: None,
+                ask: vec![ask.clone().into()],
+                offer: offer.clone().into(),
+                status: DealStatus::Expired,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
+    assert_eq!(deal.id, 2);
+    assert_eq!(deal.status, DealStatus::Expired);
+
+    // It shouldn't be possible to execute a deal that hasn't open
+    let res = execute_deal(deps.as_mut(), env.clone(), buyer_info.clone(), 2);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            3,
+            &Deal {
+                id: 3,
+                seller: seller_info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).minus_hours(1),
+                buyer: None,
+                ask: vec![ask.clone().into()],
+                offer: offer.into(),
+                status: DealStatus::Open,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 3).unwrap();
+    assert_eq!(deal.id, 3);
+    assert_eq!(deal.status, DealStatus::Open);
+
+    // It shouldn't be possible to execute a deal that has expired
+    let res = execute_deal(deps.as_mut(), env.clone(), buyer_info.clone(), 3);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::DealExpired);
+}
+
+#[test]
+pub fn test_claim() {
+    let mut deps = mock_dependencies();
+    mock_config(&mut deps);
+    let env = mock_env();
+    let offer = coin(100, "ustake");
+    let ask = coin(12, "ucosm");
+    let seller_info: MessageInfo = mock_info(SELLER, slice::from_ref(&offer));
+    let buyer_info: MessageInfo = mock_info(BUYER, slice::from_ref(&ask));
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            1,
+            &Deal {
+                id: 1,
+                seller: seller_info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
+                buyer: Some(buyer_info.sender.clone()),
+                ask: vec![ask.clone().into()],
+                offer: offer.clone().into(),
+                status: DealStatus::Open,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.id, 1);
+    assert_eq!(deal.status, DealStatus::Open);
+
+    // It shouldn't be possible to claim a open deal
+    let res = claim(deps.as_mut(), seller_info.clone(), 1);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let deal = deals()
+        .update(
+            deps.as_mut().storage,
+            1,
+            |d| -> Result<Deal, ContractError> {
+                let mut deal = d.unwrap();
+                deal.status = DealStatus::Claimable;
+                Ok(deal)
+            },
+        )
+        .unwrap();
+
+    assert_eq!(deal.status, DealStatus::Claimable);
+
+    // It shouldn't be possible to claim a deal without being the seller
+    let res = claim(deps.as_mut(), buyer_info.clone(), 1);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    // It should be possible to claim the deal
+    let res = claim(deps.as_mut(), seller_info.clone(), 1);
+    assert!(res.is_ok());
+    assert_eq!(res.unwrap().messages.len(), 1);
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Closed);
+    assert_eq!(deal.buyer, Some(buyer_info.sender.clone()));
+}
+
+#[test]
+pub fn test_withdraw() {
+    let mut deps = mock_dependencies();
+    mock_config(&mut deps);
+    let env = mock_env();
+    let offer = coin(100, "ustake");
+    let ask = coin(12, "ucosm");
+    let seller_info: MessageInfo = mock_info(SELLER, slice::from_ref(&offer));
+    let buyer_info: MessageInfo = mock_info(BUYER, slice::from_ref(&ask));
+
+    deals()
+        .save(
+            deps.as_mut().storage,
+            1,
+            &Deal {
+                id: 1,
+                seller: seller_info.sender.clone(),
+                creation_time: env.block.time,
+                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
+                buyer: Some(buyer_info.sender.clone()),
+                ask: vec![ask.clone().into()],
+                offer: offer.clone().into(),
+                status: DealStatus::Closed,
+                allow_partial: false,
+                auto_settle: false,
+                filled_offer: Uint128::zero(),
+                filled_ask: Uint128::zero(),
+                claimed_ask: Uint128::zero(),
+                allowed_buyers: vec![],
+            },
+        )
+        .unwrap();
+
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.id, 1);
+    assert_eq!(deal.status, DealStatus::Closed);
+
+    // It shouldn't be possible to withdraw a deal that is not open
+    let res = withdraw(deps.as_mut(), env.clone(), seller_info.clone(), 1);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let deal = deals()
+        .update(
+            deps.as_mut().storage,
+            1,
+            |d| -> Result<Deal, ContractError> {
+                let mut deal = d.unwrap();
+                deal.status = DealStatus::Open;
+                Ok(deal)
+            },
+        )
+        .unwrap();
+
+    assert_eq!(deal.status, DealStatus::Open);
+
+    // It shouldn't be possible to withdraw a deal without being the seller
+    let res = withdraw(deps.as_mut(), env.clone(), seller_info.clone(), 1);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    // it shouldn't be possible to withdraw if the deal is not expired
+    let res = withdraw(deps.as_mut(), env.clone(), seller_info.clone(), 1);
+    assert!(res.is_err());
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let deal = deals()
+        .update(
+            deps.as_mut().storage,
+            1,
+            |d| -> Result<Deal, ContractError> {
+                let mut deal = d.unwrap();
+                deal.end_time = Timestamp::from_seconds(env.block.time.seconds()).minus_hours(1);
+                Ok(deal)
+            },
+        )
+        .unwrap();
+
+    assert!(Expiration::AtTime(deal.end_time).is_expired(&env.block));
+
+    // It should be possible to withdraw the deal
+    let res = withdraw(deps.as_mut(), env, seller_info.clone(), 1);
+    assert!(res.is_ok());
+    assert_eq!(res.unwrap().messages.len(), 1);
+}
+
+#[test]
+pub fn test_fees() {
+    let (mut deps, env, info) = do_instantiate();
+
+    // fees above the cap are rejected
+    let msg = UpdateConfigMsg {
+        offer_fee_bps: Some(1_001),
+        ..Default::default()
+    };
+    let res = update_config(deps.as_mut(), info.clone(), msg);
+    assert_eq!(res.unwrap_err(), ContractError::InvalidFee(1_000));
+
+    let res = instantiate(
+        mock_dependencies().as_mut(),
+        env.clone(),
+        info.clone(),
+        InstantiateMsg {
+            owner: "owner".to_string(),
+            duration_policy: DurationPolicy::Range { min: 300, max: 500 },
+            fee_collector: "fee_collector".to_string(),
+            offer_fee_bps: 0,
+            ask_fee_bps: 1_001,
+            pauser: None,
+        },
+    );
+    assert_eq!(res.unwrap_err(), ContractError::InvalidFee(1_000));
+
+    let msg = UpdateConfigMsg {
+        offer_fee_bps: Some(100),
+        ask_fee_bps: Some(250),
+        ..Default::default()
+    };
+    update_config(deps.as_mut(), info, msg).unwrap();
+
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(1_000, "ustake")]),
+        CreateDealMsg {
+            offer: vec![coin(1_000, "ustake")],
+            ask: vec![coin(400, "ucosm").into()],
+            duration: 500,
+            end_time: None,
+            allow_partial: false,
+            auto_settle: false,
+            allowed_buyers: None,
+        },
+    )
+    .unwrap();
+
+    // the buyer receives the offer minus the offer fee
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(400, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages
+            .iter()
+            .map(|m| m.msg.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: BUYER.to_string(),
+                amount: vec![coin(990, "ustake")],
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "fee_collector".to_string(),
+                amount: vec![coin(10, "ustake")],
+            }),
+        ]
+    );
+    let attrs = &res.events[0].attributes;
+    assert!(attrs.contains(&attr("offer_fee", "10ustake")));
+    assert!(attrs.contains(&attr("offer_net", "990ustake")));
+
+    // the seller claims the ask minus the ask fee
+    let res = claim(deps.as_mut(), mock_info(SELLER, &[]), 1).unwrap();
+    assert_eq!(
+        res.messages
+            .iter()
+            .map(|m| m.msg.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: SELLER.to_string(),
+                amount: vec![coin(390, "ucosm")],
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "fee_collector".to_string(),
+                amount: vec![coin(10, "ucosm")],
+            }),
+        ]
+    );
+}
+
+#[test]
+pub fn test_auto_settle() {
+    let (mut deps, env, _info) = do_instantiate();
+
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake")]),
+        CreateDealMsg {
+            offer: vec![coin(100, "ustake")],
+            ask: vec![coin(50, "ucosm").into()],
+            duration: 500,
+            end_time: None,
+            allow_partial: false,
+            auto_settle: true,
+            allowed_buyers: None,
+        },
+    )
+    .unwrap();
+
+    // both sides are paid in the same transaction
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(50, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages
+            .iter()
+            .map(|m| m.msg.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: BUYER.to_string(),
+                amount: vec![coin(100, "ustake")],
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: SELLER.to_string(),
+                amount: vec![coin(50, "ucosm")],
+            }),
+        ]
+    );
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Closed);
+
+    // nothing is left to claim
+    let res = claim(deps.as_mut(), mock_info(SELLER, &[]), 1);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake")]),
+        CreateDealMsg {
+            offer: vec![coin(100, "ustake")],
+            ask: vec![coin(50, "ucosm").into()],
+            duration: 500,
+            end_time: None,
+            allow_partial: true,
+            auto_settle: true,
+            allowed_buyers: None,
+        },
+    )
+    .unwrap();
+
+    // every partial fill pays the seller right away
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(20, "ucosm")]),
+        2,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[1].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(20, "ucosm")],
+        })
+    );
+    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
+    assert_eq!(deal.status, DealStatus::Open);
+    assert_eq!(deal.claimed_ask, deal.filled_ask);
+
+    let res = claim(deps.as_mut(), mock_info(SELLER, &[]), 2);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    execute_deal(
+        deps.as_mut(),
+        env,
+        mock_info(BUYER, &[coin(30, "ucosm")]),
+        2,
+    )
+    .unwrap();
+    let deal = deals().load(deps.as_ref().storage, 2).unwrap();
+    assert_eq!(deal.status, DealStatus::Closed);
+}
+
+#[test]
+pub fn test_process_expired() {
+    let (mut deps, mut env, _info) = do_instantiate();
+
+    for (seller, offer, duration) in [
+        (SELLER, vec![coin(100, "ustake")], 300),
+        (SELLER, vec![coin(5, "uatom"), coin(20, "ustake")], 300),
+        ("another_seller", vec![coin(7, "ustake")], 300),
+        (SELLER, vec![coin(1, "ustake")], 500),
+    ] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(seller, &offer),
+            CreateDealMsg {
+                offer: offer.clone(),
+                ask: vec![coin(10, "ucosm").into()],
+                duration,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
+            },
+        )
+        .unwrap();
+    }
+
+    // nothing is expired yet
+    let res = process_expired(deps.as_mut(), env.clone(), mock_info("anyone", &[]), None).unwrap();
+    assert!(res.messages.is_empty());
+
+    env.block.time = env.block.time.plus_seconds(400);
+
+    // the limit caps how many deals are processed
+    let res = process_expired(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        Some(1),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: SELLER.to_string(),
+            amount: vec![coin(100, "ustake")],
+        })
+    );
+    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
+    assert_eq!(deal.status, DealStatus::Expired);
+
+    // refunds are aggregated per seller
+    let res = process_expired(deps.as_mut(), env.clone(), mock_info("anyone", &[]), None).unwrap();
+    assert_eq!(
+        res.messages
+            .iter()
+            .map(|m| m.msg.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "another_seller".to_string(),
+                amount: vec![coin(7, "ustake")],
+            }),
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: SELLER.to_string(),
+                amount: vec![coin(5, "uatom"), coin(20, "ustake")],
+            }),
+        ]
+    );
+    assert!(res.events[0].attributes.contains(&attr("ids", "2,3")));
+
+    // the deal that is still running is left untouched
+    let deal = deals().load(deps.as_ref().storage, 4).unwrap();
+    assert_eq!(deal.status, DealStatus::Open);
+
+    // the seller can't withdraw an already refunded deal
+    let res = withdraw(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 2);
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let res = process_expired(
+        deps.as_mut(),
+        env,
+        mock_info("anyone", &[coin(1, "ucosm")]),
+        None,
+    );
+    assert!(res.is_err());
+}
+
+#[test]
+pub fn test_process_expired_skips_closed_deals() {
+    let (mut deps, mut env, _info) = do_instantiate();
+
+    // closed deals that end before the open ones
+    for _ in 0..20 {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &[coin(10, "ustake")]),
+            CreateDealMsg {
+                offer: vec![coin(10, "ustake")],
+                ask: vec![coin(1, "ucosm").into()],
+                duration: 300,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
+            },
+        )
+        .unwrap();
+    }
+    for id in 1..=20 {
+        cancel_deal(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), id).unwrap();
+    }
+    for _ in 0..2 {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &[coin(10, "ustake")]),
+            CreateDealMsg {
+                offer: vec![coin(10, "ustake")],
+                ask: vec![coin(1, "ucosm").into()],
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
+            },
+        )
+        .unwrap();
+    }
+
+    // only the open deals are left for the crank to walk
+    let open = deals()
+        .idx
+        .refundable
+        .sub_prefix(DealStatus::Open.as_string())
+        .keys(deps.as_ref().storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(open, vec![21, 22]);
+
+    env.block.time = env.block.time.plus_seconds(600);
+    let res = process_expired(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        Some(1),
+    )
+    .unwrap();
+    assert!(res.events[0].attributes.contains(&attr("ids", "21")));
+
+    let res = process_expired(deps.as_mut(), env, mock_info("anyone", &[]), None).unwrap();
+    assert!(res.events[0].attributes.contains(&attr("ids", "22")));
+    let open = deals()
+        .idx
+        .refundable
+        .sub_prefix(DealStatus::Open.as_string())
+        .keys(deps.as_ref().storage, None, None, Order::Ascending)
+        .count();
+    assert_eq!(open, 0);
+}
+
+#[test]
+pub fn test_migrate() {
+    let mut deps = mock_dependencies();
+    let env = mock_env();
+
+    // a different contract can't be migrated
+    set_contract_version(deps.as_mut().storage, "crates.io:other", "0.1.2").unwrap();
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { deal_limit: None });
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InvalidMigration("crates.io:other".to_string())
+    );
+
+    // newer versions can't be downgraded
+    set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { deal_limit: None });
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::CannotDowngrade("99.0.0".to_string(), CONTRACT_VERSION.to_string())
+    );
+
+    // state from 0.1 is upgraded to the current layout
+    set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.2").unwrap();
+
+    // durations 0.1 accepted but that would block every deal can't be migrated
+    for duration_range in [vec![], vec![300, 0]] {
+        v0_1::CONFIG
+            .save(
+                deps.as_mut().storage,
+                &v0_1::Config {
+                    owner: Addr::unchecked("owner"),
+                    duration_range,
+                },
+            )
+            .unwrap();
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { deal_limit: None });
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::InvalidDurationPolicy(
+                "durations must be non empty and greater than zero".to_string()
+            )
+        );
+    }
+
+    v0_1::CONFIG
+        .save(
+            deps.as_mut().storage,
+            &v0_1::Config {
+                owner: Addr::unchecked("owner"),
+                duration_range: vec![500, 300],
+            },
+        )
+        .unwrap();
+    for id in 1..=3 {
+        v0_1::DEALS
+            .save(
+                deps.as_mut().storage,
+                id,
+                &v0_1::Deal {
+                    id,
+                    seller: Addr::unchecked(SELLER),
+                    buyer: None,
+                    offer: coin(100, "ustake"),
+                    ask: coin(12, "ucosm"),
+                    status: DealStatus::Open,
+                    creation_time: env.block.time,
+                    end_time: env.block.time.plus_seconds(300),
+                },
+            )
+            .unwrap();
+    }
+
+    // the deals past the limit are left for later
+    let res = migrate(
+        deps.as_mut(),
+        env.clone(),
+        MigrateMsg {
+            deal_limit: Some(2),
+        },
+    )
+    .unwrap();
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("pending_deals", "true")));
+    assert!(deals().load(deps.as_ref().storage, 3).is_err());
+
+    // new deals don't reuse the ids of the legacy ones
+    assert_eq!(ID_COUNT.load(deps.as_ref().storage).unwrap(), 4);
+
+    let res = migrate_deals(deps.as_mut(), mock_info("anyone", &[]), None).unwrap();
+    assert!(res.events[0].attributes.contains(&attr("upgraded", "1")));
+    assert!(res.events[0]
+        .attributes
+        .contains(&attr("pending_deals", "false")));
+    let res = migrate_deals(deps.as_mut(), mock_info("anyone", &[]), None).unwrap();
+    assert!(res.events[0].attributes.contains(&attr("upgraded", "0")));
+
+    let version = get_contract_version(deps.as_ref().storage).unwrap();
+    assert_eq!(version.version, CONTRACT_VERSION);
+
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.fee_collector, Addr::unchecked("owner"));
+    let ownership = OWNERSHIP.load(deps.as_ref().storage).unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked("owner")));
+    assert_eq!(cfg.offer_fee_bps, 0);
+
+    let deal = query_deal_by_id(deps.as_ref(), env.clone(), 1).unwrap();
+    assert_eq!(deal.offer, coin(100, "ustake").into());
+    assert_eq!(deal.ask, vec![coin(12, "ucosm").into()]);
+
+    // the indexes point to the upgraded deal
+    let filters = QueryFilter {
+        seller: Some(SELLER.to_string()),
+        status: Some(DealStatus::Open),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(res.len(), 3);
+    assert_eq!(res[0], deal);
+
+    // the upgraded deal is counted
+    let filters = QueryFilter {
+        seller: Some(SELLER.to_string()),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None).unwrap();
+    assert_eq!(res.total, Some(3));
+
+    // the upgraded deal can be traded
+    let res = execute_deal(
+        deps.as_mut(),
+        env,
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    );
+    assert!(res.is_ok());
+}
+
+#[test]
+pub fn test_ownership() {
+    let (mut deps, mut env, info) = do_instantiate();
+
+    // only the owner can transfer ownership
+    let action = OwnershipAction::TransferOwnership {
+        new_owner: "new_owner".to_string(),
+        expiry: None,
+    };
+    let res = update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("new_owner", &[]),
+        action,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    // there is nothing to accept yet
+    let res = update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("new_owner", &[]),
+        OwnershipAction::AcceptOwnership,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::NoPendingOwner);
+
+    // the expiry can't be in the past
+    let action = OwnershipAction::TransferOwnership {
+        new_owner: "new_owner".to_string(),
+        expiry: Some(Expiration::AtHeight(env.block.height)),
+    };
+    let res = update_ownership(deps.as_mut(), env.clone(), info.clone(), action);
+    assert_eq!(res.unwrap_err(), ContractError::InvalidExpiration);
+
+    let action = OwnershipAction::TransferOwnership {
+        new_owner: "new_owner".to_string(),
+        expiry: Some(Expiration::AtHeight(env.block.height + 10)),
+    };
+    update_ownership(deps.as_mut(), env.clone(), info.clone(), action).unwrap();
+
+    // the owner doesn't change until the transfer is accepted
+    let ownership = query_ownership(deps.as_ref()).unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked("owner")));
+    assert_eq!(ownership.pending_owner, Some(Addr::unchecked("new_owner")));
+
+    // only the pending owner can accept
+    let res = update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("someone", &[]),
+        OwnershipAction::AcceptOwnership,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    // the transfer can't be accepted once expired
+    env.block.height += 10;
+    let res = update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("new_owner", &[]),
+        OwnershipAction::AcceptOwnership,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::OwnershipTransferExpired);
+
+    let action = OwnershipAction::TransferOwnership {
+        new_owner: "new_owner".to_string(),
+        expiry: None,
+    };
+    update_ownership(deps.as_mut(), env.clone(), info.clone(), action).unwrap();
+    update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("new_owner", &[]),
+        OwnershipAction::AcceptOwnership,
+    )
+    .unwrap();
+
+    let ownership = query_ownership(deps.as_ref()).unwrap();
+    assert_eq!(
+        ownership,
+        Ownership {
+            owner: Some(Addr::unchecked("new_owner")),
+            pending_owner: None,
+            pending_expiry: None,
+        }
+    );
+
+    // once renounced nobody can update the config
+    update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("new_owner", &[]),
+        OwnershipAction::RenounceOwnership,
+    )
+    .unwrap();
+    assert_eq!(query_ownership(deps.as_ref()).unwrap().owner, None);
+
+    let res = update_config(
+        deps.as_mut(),
+        mock_info("new_owner", &[]),
+        UpdateConfigMsg::default(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+}
+
+#[test]
+pub fn test_pause() {
+    let (mut deps, env, info) = do_instantiate();
+
+    let deal_msg = CreateDealMsg {
+        offer: vec![coin(100, "ustake")],
+        ask: vec![coin(12, "ucosm").into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+    let seller_info = mock_info(SELLER, &[coin(100, "ustake")]);
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        seller_info.clone(),
+        deal_msg.clone(),
+    )
+    .unwrap();
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        seller_info.clone(),
+        deal_msg.clone(),
+    )
+    .unwrap();
+
+    // only the owner or the pauser can pause
+    let res = pause(deps.as_mut(), mock_info("pauser", &[]));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    let msg = UpdateConfigMsg {
+        pauser: Some("pauser".to_string()),
+        ..Default::default()
+    };
+    update_config(deps.as_mut(), info.clone(), msg).unwrap();
+
+    let res = pause(deps.as_mut(), mock_info("pauser", &[])).unwrap();
+    assert_eq!(res.events[0].ty, "OtcWasm.v1.MsgPause");
+    assert!(query_paused(deps.as_ref()).unwrap());
+
+    // trading is stopped
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        seller_info.clone(),
+        deal_msg.clone(),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Paused);
+
+    let res = execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Paused);
+
+    let msg = CreateBidMsg {
+        ask: coin(100, "ustake").into(),
+        duration: 500,
+        allowed_seller: None,
+    };
+    let res = create_bid(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        msg,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Paused);
+
+    // but funds can still be taken out
+    cancel_deal(deps.as_mut(), env.clone(), seller_info.clone(), 1).unwrap();
+
+    // only the owner can unpause
+    let res = unpause(deps.as_mut(), mock_info("pauser", &[]));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    // ownership can't be renounced while nobody else could unpause
+    let res = update_ownership(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        OwnershipAction::RenounceOwnership,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Paused);
+
+    unpause(deps.as_mut(), info.clone()).unwrap();
+    assert!(!query_paused(deps.as_ref()).unwrap());
+
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        2,
+    )
+    .unwrap();
+
+    // only the owner can remove the pauser
+    let res = remove_pauser(deps.as_mut(), mock_info("pauser", &[]));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    remove_pauser(deps.as_mut(), info.clone()).unwrap();
+    assert_eq!(query_config(deps.as_ref()).unwrap().pauser, None);
+    let res = pause(deps.as_mut(), mock_info("pauser", &[]));
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    // renouncing ownership removes the pauser too
+    let msg = UpdateConfigMsg {
+        pauser: Some("pauser".to_string()),
+        ..Default::default()
+    };
+    update_config(deps.as_mut(), info.clone(), msg).unwrap();
+    update_ownership(deps.as_mut(), env, info, OwnershipAction::RenounceOwnership).unwrap();
+    assert_eq!(query_config(deps.as_ref()).unwrap().pauser, None);
+}
+
+#[test]
+pub fn test_denom_lists() {
+    let (mut deps, env, info) = do_instantiate();
+
+    let deal_msg = |offer: &str, ask: &str| CreateDealMsg {
+        offer: vec![coin(100, offer)],
+        ask: vec![coin(12, ask).into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+
+    // only the owner can manage the lists
+    let res = update_denoms(
+        deps.as_mut(),
+        mock_info(SELLER, &[]),
+        DenomList::Deny,
+        vec![UncheckedDenom::Native("uscam".to_string())],
+        vec![],
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    update_denoms(
+        deps.as_mut(),
+        info.clone(),
+        DenomList::Deny,
+        vec![UncheckedDenom::Native("uscam".to_string())],
+        vec![],
+    )
+    .unwrap();
+
+    // denied denoms can't be offered or asked
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "uscam")]),
+        deal_msg("uscam", "ucosm"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DenomNotAllowed("uscam".to_string())
+    );
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake")]),
+        deal_msg("ustake", "uscam"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DenomNotAllowed("uscam".to_string())
+    );
+
+    // other denoms can be traded while the allowlist is empty
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "uatom")]),
+        deal_msg("uatom", "ucosm"),
+    )
+    .unwrap();
+
+    update_denoms(
+        deps.as_mut(),
+        info.clone(),
+        DenomList::Allow,
+        vec![
+            UncheckedDenom::Native("ucosm".to_string()),
+            UncheckedDenom::Native("ustake".to_string()),
+            UncheckedDenom::Native("uosmo".to_string()),
+        ],
+        vec![UncheckedDenom::Native("uosmo".to_string())],
+    )
+    .unwrap();
+
+    // once set only allowed denoms can be traded
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "uatom")]),
+        deal_msg("uatom", "ucosm"),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DenomNotAllowed("uatom".to_string())
+    );
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake")]),
+        deal_msg("ustake", "ucosm"),
+    )
+    .unwrap();
+
+    let res = query_denoms(deps.as_ref(), DenomList::Allow, None).unwrap();
+    assert_eq!(res.items, vec!["ucosm".to_string(), "ustake".to_string()]);
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(1),
+        descending: None,
+    };
+    let page = query_denoms(deps.as_ref(), DenomList::Allow, Some(query_options)).unwrap();
+    assert_eq!(page.items, vec!["ucosm".to_string()]);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(1),
+        descending: None,
+    };
+    let page = query_denoms(deps.as_ref(), DenomList::Allow, Some(query_options)).unwrap();
+    assert_eq!(page.items, vec!["ustake".to_string()]);
+    assert_eq!(page.next_cursor, None);
+    let res = query_denoms(deps.as_ref(), DenomList::Deny, None).unwrap();
+    assert_eq!(res.items, vec!["uscam".to_string()]);
+
+    // entries are validated so they match the denoms of deals
+    let res = update_denoms(
+        deps.as_mut(),
+        info.clone(),
+        DenomList::Deny,
+        vec![UncheckedDenom::Native("u".to_string())],
+        vec![],
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Denom(DenomError::NativeDenomLength { len: 1 })
+    );
+    let res = update_denoms(
+        deps.as_mut(),
+        info.clone(),
+        DenomList::Deny,
+        vec![UncheckedDenom::Cw20("Token".to_string())],
+        vec![],
+    );
+    assert!(res.is_err());
+    update_denoms(
+        deps.as_mut(),
+        info,
+        DenomList::Deny,
+        vec![UncheckedDenom::Cw20("token".to_string())],
+        vec![],
+    )
+    .unwrap();
+    let res = query_denoms(deps.as_ref(), DenomList::Deny, None).unwrap();
+    assert_eq!(res.items, vec!["token".to_string(), "uscam".to_string()]);
+}
+
+#[test]
+pub fn test_denom_lists_nft() {
+    let (mut deps, env, info) = do_instantiate();
+
+    let nft_deal = |deps: &mut OwnedDeps<MemoryStorage, MockApi, MockQuerier>, collection: &str| {
+        let cw721_msg = Cw721ReceiveMsg {
+            sender: SELLER.to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&ReceiveNftMsg::CreateDeal(CreateDealMsg {
+                offer: vec![],
+                ask: vec![coin(12, "ucosm").into()],
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
+            }))
+            .unwrap(),
+        };
+        receive_nft(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(collection, &[]),
+            cw721_msg,
+        )
+    };
+
+    // collections are listed by address like cw20 tokens
+    update_denoms(
+        deps.as_mut(),
+        info.clone(),
+        DenomList::Deny,
+        vec![UncheckedDenom::Cw20("scam_collection".to_string())],
+        vec![],
+    )
+    .unwrap();
+    let res = nft_deal(&mut deps, "scam_collection");
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DenomNotAllowed("scam_collection".to_string())
+    );
+    nft_deal(&mut deps, "collection").unwrap();
+
+    update_denoms(
+        deps.as_mut(),
+        info.clone(),
+        DenomList::Allow,
+        vec![UncheckedDenom::Native("ucosm".to_string())],
+        vec![],
+    )
+    .unwrap();
+    let res = nft_deal(&mut deps, "collection");
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DenomNotAllowed("collection".to_string())
+    );
+
+    update_denoms(
+        deps.as_mut(),
+        info,
+        DenomList::Allow,
+        vec![UncheckedDenom::Cw20("collection".to_string())],
+        vec![],
+    )
+    .unwrap();
+    nft_deal(&mut deps, "collection").unwrap();
+}
+
+#[test]
+pub fn test_trade_limits() {
+    let (mut deps, env, info) = do_instantiate();
+
+    let deal_msg = |offer: Coin, ask: Coin| CreateDealMsg {
+        offer: vec![offer],
+        ask: vec![ask.into()],
+        duration: 500,
+        end_time: None,
+        allow_partial: false,
+        auto_settle: false,
+        allowed_buyers: None,
+    };
+
+    let res = set_trade_limit(
+        deps.as_mut(),
+        info.clone(),
+        UncheckedDenom::Native("ucosm".to_string()),
+        Some(TradeLimit {
+            min_amount: Some(Uint128::new(1_000)),
+            max_amount: Some(Uint128::new(10)),
+        }),
+    );
+    assert_eq!(res.unwrap_err(), ContractError::InvalidTradeLimit);
+
+    // the denom is validated so it matches the denoms of deals
+    let res = set_trade_limit(
+        deps.as_mut(),
+        info.clone(),
+        UncheckedDenom::Native("u".to_string()),
+        None,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Denom(DenomError::NativeDenomLength { len: 1 })
+    );
+    let res = set_trade_limit(
+        deps.as_mut(),
+        info.clone(),
+        UncheckedDenom::Cw20("Token".to_string()),
+        None,
+    );
+    assert!(res.is_err());
+
+    let res = set_trade_limit(
+        deps.as_mut(),
+        mock_info(SELLER, &[]),
+        UncheckedDenom::Native("ucosm".to_string()),
+        None,
+    );
+    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+
+    set_trade_limit(
+        deps.as_mut(),
+        info.clone(),
+        UncheckedDenom::Native("ucosm".to_string()),
+        Some(TradeLimit {
+            min_amount: Some(Uint128::new(10)),
+            max_amount: Some(Uint128::new(1_000)),
+        }),
+    )
+    .unwrap();
+    set_trade_limit(
+        deps.as_mut(),
+        info.clone(),
+        UncheckedDenom::Native("ustake".to_string()),
+        Some(TradeLimit {
+            min_amount: None,
+            max_amount: Some(Uint128::new(500)),
+        }),
+    )
+    .unwrap();
+
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake")]),
+        deal_msg(coin(100, "ustake"), coin(5, "ucosm")),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::AmountBelowMinimum("5ucosm".to_string(), "10ucosm".to_string())
+    );
+
+    let res = create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(600, "ustake")]),
+        deal_msg(coin(600, "ustake"), coin(50, "ucosm")),
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::AmountAboveMaximum("600ustake".to_string(), "500ustake".to_string())
+    );
+
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(500, "ustake")]),
+        deal_msg(coin(500, "ustake"), coin(10, "ucosm")),
+    )
+    .unwrap();
+
+    let res = query_trade_limit(deps.as_ref(), "ustake".to_string()).unwrap();
+    assert_eq!(
+        res,
+        Some(TradeLimit {
+            min_amount: None,
+            max_amount: Some(Uint128::new(500)),
+        })
+    );
+    let res = query_trade_limits(deps.as_ref(), None).unwrap().items;
+    assert_eq!(
+        res.iter().map(|l| l.denom.as_str()).collect::<Vec<_>>(),
+        vec!["ucosm", "ustake"]
+    );
+
+    // removing the limit lifts it
+    set_trade_limit(
+        deps.as_mut(),
+        info,
+        UncheckedDenom::Native("ustake".to_string()),
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        query_trade_limit(deps.as_ref(), "ustake".to_string()).unwrap(),
+        None
+    );
+    create_deal(
+        deps.as_mut(),
+        env,
+        mock_info(SELLER, &[coin(600, "ustake")]),
+        deal_msg(coin(600, "ustake"), coin(50, "ucosm")),
+    )
+    .unwrap();
+}
+
+#[test]
+pub fn test_query_deals_by_pair() {
+    let (mut deps, env, _info) = do_instantiate();
+
+    for (offer, ask) in [
+        (vec![coin(100, "ustake")], vec![coin(30, "ucosm")]),
+        (vec![coin(100, "ustake")], vec![coin(10, "ucosm")]),
+        (vec![coin(100, "ustake")], vec![coin(20, "ucosm")]),
+        (vec![coin(100, "uatom")], vec![coin(5, "ucosm")]),
+        (
+            vec![coin(100, "uatom"), coin(100, "ustake")],
+            vec![coin(5, "ucosm")],
+        ),
+    ] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &offer),
+            CreateDealMsg {
+                offer: offer.clone(),
+                ask: ask.into_iter().map(Into::into).collect(),
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
+            },
+        )
+        .unwrap();
+    }
+
+    let filters = QueryFilter {
+        pair: Some(Pair {
+            offer_denom: "ustake".to_string(),
+            ask_denom: "ucosm".to_string(),
+        }),
+        ..Default::default()
+    };
+    let ids = |deals: Vec<Deal>| deals.iter().map(|d| d.id).collect::<Vec<_>>();
+
+    // deals of the pair are sorted by price, baskets are left out
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters.clone(), None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![2, 3, 1]);
+
+    // pages resume after the price of the last deal
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(2),
+        descending: None,
+    };
+    let page = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap();
+    assert_eq!(ids(page.items), vec![2, 3]);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(2),
+        descending: None,
+    };
+    let page = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap();
+    assert_eq!(ids(page.items), vec![1]);
+    assert_eq!(page.next_cursor, None);
+
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(1),
+        descending: Some(true),
+    };
+    let page = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap();
+    assert_eq!(ids(page.items), vec![1]);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: None,
+        descending: Some(true),
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, Some(query_options))
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![3, 2]);
 
-    // It shouldn't be possible to execute a deal that hasn't open
-    let res = execute_deal(deps.as_mut(), env.clone(), buyer_info.clone(), 2);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+    // the pair can be combined with other filters
+    cancel_deal(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 2).unwrap();
+    let filters = QueryFilter {
+        status: Some(DealStatus::Open),
+        pair: Some(Pair {
+            offer_denom: "ustake".to_string(),
+            ask_denom: "ucosm".to_string(),
+        }),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env, filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![3, 1]);
+}
 
-    deals()
-        .save(
-            deps.as_mut().storage,
-            3,
-            &Deal {
-                id: 3,
-                seller: seller_info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).minus_hours(1),
-                buyer: None,
-                ask: ask.clone(),
-                offer,
-                status: DealStatus::Open,
+#[test]
+pub fn test_query_deals_by_combined_filters() {
+    let (mut deps, mut env, _info) = do_instantiate();
+
+    for (offer, ask) in [
+        (vec![coin(100, "ustake")], vec![coin(30, "ucosm")]),
+        (vec![coin(500, "ustake")], vec![coin(10, "uatom")]),
+        (
+            vec![coin(100, "uatom"), coin(200, "ustake")],
+            vec![coin(5, "ucosm")],
+        ),
+        (vec![coin(300, "uatom")], vec![coin(50, "ucosm")]),
+    ] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &offer),
+            CreateDealMsg {
+                offer: offer.clone(),
+                ask: ask.into_iter().map(Into::into).collect(),
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
             },
         )
         .unwrap();
+        env.block.time = env.block.time.plus_seconds(10);
+    }
+    let ids = |deals: Vec<Deal>| deals.iter().map(|d| d.id).collect::<Vec<_>>();
 
-    let deal = deals().load(deps.as_ref().storage, 3).unwrap();
-    assert_eq!(deal.id, 3);
-    assert_eq!(deal.status, DealStatus::Open);
+    // baskets match any of their denoms
+    let filters = QueryFilter {
+        offer_denom: Some("ustake".to_string()),
+        ask_denom: Some("ucosm".to_string()),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![1, 3]);
 
-    // It shouldn't be possible to execute a deal that has expired
-    let res = execute_deal(deps.as_mut(), env.clone(), buyer_info.clone(), 3);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::DealExpired);
+    // amount ranges apply to the filtered denom
+    let filters = QueryFilter {
+        offer_denom: Some("ustake".to_string()),
+        offer_amount: Some(AmountRange {
+            min: Some(Uint128::new(200)),
+            max: None,
+        }),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![2, 3]);
+
+    let filters = QueryFilter {
+        seller: Some(SELLER.to_string()),
+        status: Some(DealStatus::Open),
+        ask_amount: Some(AmountRange {
+            min: Some(Uint128::new(10)),
+            max: Some(Uint128::new(30)),
+        }),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![1, 2]);
+
+    // creation time bounds are inclusive
+    let start = mock_env().block.time;
+    let filters = QueryFilter {
+        creation_time: Some(TimeRange {
+            start: Some(start.plus_seconds(10)),
+            end: Some(start.plus_seconds(20)),
+        }),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![2, 3]);
+
+    // end time ranges page through the end time index
+    let filters = QueryFilter {
+        end_time: Some(TimeRange {
+            start: Some(start.plus_seconds(510)),
+            end: None,
+        }),
+        ..Default::default()
+    };
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(1),
+        descending: None,
+    };
+    let page = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap();
+    assert_eq!(ids(page.items), vec![2]);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(1),
+        descending: None,
+    };
+    let res = query_deals_by_filters(
+        deps.as_ref(),
+        env.clone(),
+        filters.clone(),
+        Some(query_options),
+    )
+    .unwrap()
+    .items;
+    assert_eq!(ids(res), vec![3]);
+
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: None,
+        descending: Some(true),
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, Some(query_options))
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![4, 3, 2]);
+
+    // denoms combine with the status index
+    let filters = QueryFilter {
+        offer_denom: Some("uatom".to_string()),
+        status: Some(DealStatus::Open),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env, filters, None)
+        .unwrap()
+        .items;
+    assert_eq!(ids(res), vec![3, 4]);
 }
 
 #[test]
-pub fn test_claim() {
-    let mut deps = mock_dependencies();
-    let env = mock_env();
-    let offer = coin(100, "ustake");
-    let ask = coin(12, "ucosm");
-    let seller_info: MessageInfo = mock_info(SELLER, &[offer.clone()]);
-    let buyer_info: MessageInfo = mock_info(BUYER, &[ask.clone()]);
+pub fn test_query_deals_by_partial_buyers() {
+    let (mut deps, env, _info) = do_instantiate();
 
-    deals()
-        .save(
-            deps.as_mut().storage,
-            1,
-            &Deal {
-                id: 1,
-                seller: seller_info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
-                buyer: Some(buyer_info.sender.clone()),
-                ask: ask.clone(),
-                offer: offer.clone(),
-                status: DealStatus::Open,
+    create_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(SELLER, &[coin(100, "ustake")]),
+        CreateDealMsg {
+            offer: vec![coin(100, "ustake")],
+            ask: vec![coin(10, "ucosm").into()],
+            duration: 500,
+            end_time: None,
+            allow_partial: true,
+            auto_settle: false,
+            allowed_buyers: None,
+        },
+    )
+    .unwrap();
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(4, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("another_buyer", &[coin(6, "ucosm")]),
+        1,
+    )
+    .unwrap();
+
+    // the deal only keeps its last buyer but is found by both
+    let deal = query_deal_by_id(deps.as_ref(), env.clone(), 1).unwrap();
+    assert_eq!(deal.buyer, Some(Addr::unchecked("another_buyer")));
+    for buyer in [BUYER, "another_buyer"] {
+        let filters = QueryFilter {
+            buyer: Some(buyer.to_string()),
+            status: Some(DealStatus::Claimable),
+            ..Default::default()
+        };
+        let res = query_deals_by_filters(deps.as_ref(), env.clone(), filters, None)
+            .unwrap()
+            .items;
+        assert_eq!(res, vec![deal.clone()]);
+    }
+
+    let filters = QueryFilter {
+        buyer: Some("someone".to_string()),
+        ..Default::default()
+    };
+    let res = query_deals_by_filters(deps.as_ref(), env, filters, None)
+        .unwrap()
+        .items;
+    assert!(res.is_empty());
+}
+
+#[test]
+pub fn test_query_deals_scan_limit() {
+    let (mut deps, env, _info) = do_instantiate();
+
+    for ask in ["ucosm"; 505].into_iter().chain(["uatom"]) {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &[coin(100, "ustake")]),
+            CreateDealMsg {
+                offer: vec![coin(100, "ustake")],
+                ask: vec![coin(12, ask).into()],
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
             },
         )
         .unwrap();
+    }
 
-    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(deal.id, 1);
-    assert_eq!(deal.status, DealStatus::Open);
+    // a filter matching few deals stops after the scan cap with a cursor to resume
+    let filters = QueryFilter {
+        ask_denom: Some("uatom".to_string()),
+        ..Default::default()
+    };
+    let page = query_deals_by_filters(deps.as_ref(), env.clone(), filters.clone(), None).unwrap();
+    assert!(page.items.is_empty());
+    assert!(page.next_cursor.is_some());
 
-    // It shouldn't be possible to claim a open deal
-    let res = claim(deps.as_mut(), seller_info.clone(), 1);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: None,
+        descending: None,
+    };
+    let page = query_deals_by_filters(deps.as_ref(), env, filters, Some(query_options)).unwrap();
+    assert_eq!(
+        page.items.iter().map(|d| d.id).collect::<Vec<_>>(),
+        vec![506]
+    );
+    assert_eq!(page.next_cursor, None);
+}
 
-    let deal = deals()
-        .update(
-            deps.as_mut().storage,
-            1,
-            |d| -> Result<Deal, ContractError> {
-                let mut deal = d.unwrap();
-                deal.status = DealStatus::Claimable;
-                Ok(deal)
+#[test]
+pub fn test_query_deal_totals() {
+    let (mut deps, env, _info) = do_instantiate();
+
+    for seller in [SELLER, SELLER, SELLER, "another_seller"] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(seller, &[coin(100, "ustake")]),
+            CreateDealMsg {
+                offer: vec![coin(100, "ustake")],
+                ask: vec![coin(12, "ucosm").into()],
+                duration: 500,
+                end_time: None,
+                allow_partial: false,
+                auto_settle: false,
+                allowed_buyers: None,
             },
         )
         .unwrap();
+    }
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    claim(deps.as_mut(), mock_info(SELLER, &[]), 1).unwrap();
+    cancel_deal(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 2).unwrap();
+
+    let total = |filters: QueryFilter| {
+        let options = QueryOptions {
+            cursor: None,
+            limit: Some(1),
+            descending: None,
+        };
+        query_deals_by_filters(deps.as_ref(), env.clone(), filters, Some(options))
+            .unwrap()
+            .total
+    };
 
-    assert_eq!(deal.status, DealStatus::Claimable);
-
-    // It shouldn't be possible to claim a deal without being the seller
-    let res = claim(deps.as_mut(), buyer_info.clone(), 1);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+    // totals count every page
+    assert_eq!(total(QueryFilter::default()), Some(4));
+    assert_eq!(
+        total(QueryFilter {
+            seller: Some(SELLER.to_string()),
+            ..Default::default()
+        }),
+        Some(3)
+    );
+    assert_eq!(
+        total(QueryFilter {
+            status: Some(DealStatus::Closed),
+            ..Default::default()
+        }),
+        Some(1)
+    );
+    assert_eq!(
+        total(QueryFilter {
+            seller: Some(SELLER.to_string()),
+            status: Some(DealStatus::Cancelled),
+            ..Default::default()
+        }),
+        Some(1)
+    );
+    assert_eq!(
+        total(QueryFilter {
+            seller: Some("another_seller".to_string()),
+            status: Some(DealStatus::Closed),
+            ..Default::default()
+        }),
+        Some(0)
+    );
 
-    // It should be possible to claim the deal
-    let res = claim(deps.as_mut(), seller_info.clone(), 1);
-    assert!(res.is_ok());
-    assert_eq!(res.unwrap().messages.len(), 1);
+    // open deals may have expired and other filters aren't counted
+    assert_eq!(
+        total(QueryFilter {
+            status: Some(DealStatus::Open),
+            ..Default::default()
+        }),
+        None
+    );
+    assert_eq!(
+        total(QueryFilter {
+            offer_denom: Some("ustake".to_string()),
+            ..Default::default()
+        }),
+        None
+    );
 
-    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(deal.status, DealStatus::Closed);
-    assert_eq!(deal.buyer, Some(buyer_info.sender.clone()));
+    let res = query_deals_by_expiration(deps.as_ref(), env.clone(), true, None).unwrap();
+    assert_eq!(res.total, Some(4));
+    let res = query_deals_by_expiration(deps.as_ref(), env, false, None).unwrap();
+    assert_eq!(res.total, None);
 }
 
 #[test]
-pub fn test_withdraw() {
-    let mut deps = mock_dependencies();
-    let env = mock_env();
-    let offer = coin(100, "ustake");
-    let ask = coin(12, "ucosm");
-    let seller_info: MessageInfo = mock_info(SELLER, &[offer.clone()]);
-    let buyer_info: MessageInfo = mock_info(BUYER, &[ask.clone()]);
-
-    deals()
-        .save(
-            deps.as_mut().storage,
-            1,
-            &Deal {
-                id: 1,
-                seller: seller_info.sender.clone(),
-                creation_time: env.block.time,
-                end_time: Timestamp::from_seconds(env.block.time.seconds()).plus_hours(1),
-                buyer: Some(buyer_info.sender.clone()),
-                ask: ask.clone(),
-                offer: offer.clone(),
-                status: DealStatus::Closed,
+pub fn test_stats() {
+    let (mut deps, mut env, _info) = do_instantiate();
+
+    for (ask, allow_partial) in [(12, false), (20, true), (12, false), (12, false)] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &[coin(100, "ustake")]),
+            CreateDealMsg {
+                offer: vec![coin(100, "ustake")],
+                ask: vec![coin(ask, "ucosm").into()],
+                duration: 500,
+                end_time: None,
+                allow_partial,
+                auto_settle: false,
+                allowed_buyers: None,
             },
         )
         .unwrap();
+    }
 
-    let deal = deals().load(deps.as_ref().storage, 1).unwrap();
-    assert_eq!(deal.id, 1);
-    assert_eq!(deal.status, DealStatus::Closed);
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    claim(deps.as_mut(), mock_info(SELLER, &[]), 1).unwrap();
+
+    // partial deals are executed once their whole ask is paid
+    for _ in 0..2 {
+        execute_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(BUYER, &[coin(10, "ucosm")]),
+            2,
+        )
+        .unwrap();
+    }
 
-    // It shouldn't be possible to withdraw a deal that is not open
-    let res = withdraw(deps.as_mut(), env.clone(), seller_info.clone(), 1);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+    cancel_deal(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 3).unwrap();
+    env.block.time = env.block.time.plus_seconds(500);
+    withdraw(deps.as_mut(), env, mock_info(SELLER, &[]), 4).unwrap();
 
-    let deal = deals()
-        .update(
-            deps.as_mut().storage,
-            1,
-            |d| -> Result<Deal, ContractError> {
-                let mut deal = d.unwrap();
-                deal.status = DealStatus::Open;
-                Ok(deal)
+    let res = query_stats(deps.as_ref()).unwrap();
+    assert_eq!(
+        res.stats,
+        Stats {
+            deals_created: 4,
+            deals_executed: 2,
+            deals_cancelled: 1,
+            deals_expired: 1,
+        }
+    );
+    assert_eq!(res.offer_volume, vec![coin(200, "ustake")]);
+    assert_eq!(res.ask_volume, vec![coin(32, "ucosm")]);
+}
+
+#[test]
+pub fn test_trade_history() {
+    let (mut deps, mut env, _info) = do_instantiate();
+    let start = env.block.time;
+
+    for (offer, ask, allow_partial) in [
+        (vec![coin(100, "ustake")], coin(20, "ucosm"), true),
+        (vec![coin(100, "ustake")], coin(30, "ucosm"), false),
+        (
+            vec![coin(100, "uatom"), coin(100, "ustake")],
+            coin(5, "ucosm"),
+            false,
+        ),
+    ] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, &offer),
+            CreateDealMsg {
+                offer: offer.clone(),
+                ask: vec![ask.into()],
+                duration: 500,
+                end_time: None,
+                allow_partial,
+                auto_settle: false,
+                allowed_buyers: None,
             },
         )
         .unwrap();
+    }
+
+    // each fill is a trade, baskets have no pair to record them in
+    for (id, paid) in [(1, 10), (1, 10), (2, 30), (3, 5)] {
+        execute_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(BUYER, &[coin(paid, "ucosm")]),
+            id,
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(10);
+    }
 
-    assert_eq!(deal.status, DealStatus::Open);
-
-    // It shouldn't be possible to withdraw a deal without being the seller
-    let res = withdraw(deps.as_mut(), env.clone(), seller_info.clone(), 1);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
-
-    // it shouldn't be possible to withdraw if the deal is not expired
-    let res = withdraw(deps.as_mut(), env.clone(), seller_info.clone(), 1);
-    assert!(res.is_err());
-    assert_eq!(res.unwrap_err(), ContractError::Unauthorized);
+    let pair = Pair {
+        offer_denom: "ustake".to_string(),
+        ask_denom: "ucosm".to_string(),
+    };
+    let res = query_last_price(deps.as_ref(), pair.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(res.price, Decimal::percent(30));
+    assert_eq!(res.trade.deal_id, 2);
+    assert_eq!(res.trade.offer, coin(100, "ustake").into());
+    assert_eq!(res.trade.time, start.plus_seconds(20));
+
+    let res = query_last_price(
+        deps.as_ref(),
+        Pair {
+            offer_denom: "uatom".to_string(),
+            ask_denom: "ucosm".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(res, None);
+
+    let deal_ids = |trades: Vec<Trade>| trades.iter().map(|t| t.deal_id).collect::<Vec<_>>();
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: Some(2),
+        descending: None,
+    };
+    let page = query_trades(deps.as_ref(), pair.clone(), None, Some(query_options)).unwrap();
+    assert_eq!(deal_ids(page.items), vec![1, 1]);
+    let query_options = QueryOptions {
+        cursor: page.next_cursor,
+        limit: Some(2),
+        descending: None,
+    };
+    let page = query_trades(deps.as_ref(), pair.clone(), None, Some(query_options)).unwrap();
+    assert_eq!(deal_ids(page.items), vec![2]);
+    assert_eq!(page.next_cursor, None);
+
+    // time bounds are inclusive
+    let time = TimeRange {
+        start: Some(start.plus_seconds(10)),
+        end: None,
+    };
+    let query_options = QueryOptions {
+        cursor: None,
+        limit: None,
+        descending: Some(true),
+    };
+    let res = query_trades(deps.as_ref(), pair, Some(time), Some(query_options)).unwrap();
+    assert_eq!(
+        res.items
+            .iter()
+            .map(|t| t.ask.amount.u128())
+            .collect::<Vec<_>>(),
+        vec![30, 10]
+    );
+}
 
-    let deal = deals()
-        .update(
-            deps.as_mut().storage,
-            1,
-            |d| -> Result<Deal, ContractError> {
-                let mut deal = d.unwrap();
-                deal.end_time = Timestamp::from_seconds(env.block.time.seconds()).minus_hours(1);
-                Ok(deal)
+#[test]
+pub fn test_query_account() {
+    let (mut deps, mut env, _info) = do_instantiate();
+
+    for (offer, ask, duration, allow_partial) in [
+        (coin(100, "ustake"), coin(12, "ucosm"), 500, false),
+        (coin(100, "uatom"), coin(20, "ucosm"), 500, true),
+        (coin(100, "ustake"), coin(12, "ucosm"), 300, false),
+        (coin(100, "uatom"), coin(12, "ucosm"), 500, false),
+        (coin(100, "ustake"), coin(12, "ucosm"), 300, false),
+    ] {
+        create_deal(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(SELLER, slice::from_ref(&offer)),
+            CreateDealMsg {
+                offer: vec![offer],
+                ask: vec![ask.into()],
+                duration,
+                end_time: None,
+                allow_partial,
+                auto_settle: false,
+                allowed_buyers: None,
             },
         )
         .unwrap();
+    }
 
-    assert!(Expiration::AtTime(deal.end_time).is_expired(&env.block));
+    let res = query_account(deps.as_ref(), env.clone(), SELLER.to_string(), None).unwrap();
+    assert_eq!(res.escrowed, vec![coin(200, "uatom"), coin(300, "ustake")]);
 
-    // It should be possible to withdraw the deal
-    let res = withdraw(deps.as_mut(), env, seller_info.clone(), 1);
-    assert!(res.is_ok());
-    assert_eq!(res.unwrap().messages.len(), 1);
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(12, "ucosm")]),
+        1,
+    )
+    .unwrap();
+    execute_deal(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(BUYER, &[coin(10, "ucosm")]),
+        2,
+    )
+    .unwrap();
+    cancel_deal(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 4).unwrap();
+    env.block.time = env.block.time.plus_seconds(400);
+
+    // the deal past its end time is awaiting withdraw
+    let res = query_account(deps.as_ref(), env.clone(), SELLER.to_string(), None).unwrap();
+    assert_eq!(
+        res,
+        AccountResponse {
+            escrowed: vec![coin(50, "uatom")],
+            claimable: vec![coin(22, "ucosm")],
+            expired: vec![coin(200, "ustake")],
+            expired_complete: true,
+            deals: DealCounts {
+                open: 1,
+                claimable: 1,
+                cancelled: 1,
+                closed: 0,
+                expired: 2,
+            },
+        }
+    );
+
+    // the limit caps how many deals past their end time are counted
+    let res = query_account(deps.as_ref(), env.clone(), SELLER.to_string(), Some(1)).unwrap();
+    assert_eq!(res.escrowed, vec![coin(50, "uatom"), coin(100, "ustake")]);
+    assert_eq!(res.expired, vec![coin(100, "ustake")]);
+    assert!(!res.expired_complete);
+    assert_eq!((res.deals.open, res.deals.expired), (2, 1));
+
+    withdraw(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 3).unwrap();
+    withdraw(deps.as_mut(), env.clone(), mock_info(SELLER, &[]), 5).unwrap();
+    claim(deps.as_mut(), mock_info(SELLER, &[]), 1).unwrap();
+    claim(deps.as_mut(), mock_info(SELLER, &[]), 2).unwrap();
+
+    let res = query_account(deps.as_ref(), env, SELLER.to_string(), None).unwrap();
+    assert_eq!(res.escrowed, vec![coin(50, "uatom")]);
+    assert_eq!(res.claimable, vec![]);
+    assert_eq!(res.expired, vec![]);
+    assert_eq!(
+        res.deals,
+        DealCounts {
+            open: 1,
+            claimable: 0,
+            cancelled: 1,
+            closed: 1,
+            expired: 2,
+        }
+    );
 }